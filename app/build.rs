@@ -8,6 +8,7 @@ declare global {
   interface Window {
     bridge: {
       send<T>(command: string, payload: any | undefined): Promise<T>;
+      on<T>(event: string, callback: (payload: T) => void): () => void;
     };
   }
 }
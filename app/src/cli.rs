@@ -0,0 +1,431 @@
+use crate::{
+    AppState,
+    config::set_path,
+    context::{load_plugin_from_json, read_manifest},
+    discovery::find_manifests,
+};
+use anyhow::{Result, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use clap::{Args, Parser, Subcommand};
+use host_pluginmanager::HostPM;
+use libcommon::debug;
+use pluginmanager::{PluginId, PluginInfo, plugin::Value};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// 插件宿主应用
+#[derive(Parser, Debug)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// 未指定子命令时作为 `run` 的参数
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 启动应用（未指定子命令时的默认行为）
+    Run(RunArgs),
+    /// 管理插件，不打开窗口
+    Plugins {
+        #[command(flatten)]
+        common: CommonArgs,
+        #[command(subcommand)]
+        command: PluginsCommand,
+    },
+    /// 调用插件方法并输出结果，不打开窗口
+    Call {
+        #[command(flatten)]
+        common: CommonArgs,
+        /// 插件 id 或名称
+        plugin: String,
+        /// 方法名
+        method: String,
+        /// 参数（JSON）
+        #[arg(default_value = "null")]
+        params: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PluginsCommand {
+    /// 列出注册表中的插件及其启用状态（不加载插件；未登记的插件见 `discover`）
+    List,
+    /// 将插件（插件包、插件描述文件或其所在文件夹）安装到插件目录
+    Install { path: PathBuf },
+    /// 卸载插件并从注册表移除；只删除安装插件时创建的文件夹，其他插件文件保留
+    Remove {
+        /// 插件 id 或名称
+        plugin: String,
+        /// 有插件依赖它时，先卸载并禁用这些插件
+        #[arg(long)]
+        force: bool,
+    },
+    /// 启用已禁用的插件
+    Enable {
+        /// 插件 id 或名称
+        plugin: String,
+    },
+    /// 禁用插件，保留在插件目录中但不再加载
+    Disable {
+        /// 插件 id 或名称
+        plugin: String,
+        /// 有插件依赖它时，先卸载并禁用这些插件
+        #[arg(long)]
+        force: bool,
+    },
+    /// 签名插件描述文件（或插件包中的 manifest.json，需在生成校验文件之前签名）
+    Sign {
+        manifest: PathBuf,
+        /// 私钥文件，内容为 base64 编码的 32 字节 ed25519 种子
+        #[arg(short, long)]
+        key: PathBuf,
+    },
+    /// 列出项目、用户和系统插件目录中的插件及其状态，不加载
+    Discover,
+    /// 登记并加载插件目录中发现的插件，之后随注册表恢复
+    Register { manifest: PathBuf },
+    /// 扫描并加载指定目录下的插件，默认为插件目录；输出每个描述文件的 id、结果、耗时、路径及错误
+    Scan { dir: Option<PathBuf> },
+    /// 授权插件访问指定路径（含子路径）
+    Grant {
+        /// 插件 id 或名称
+        plugin: String,
+        path: PathBuf,
+        /// 拒绝访问，插件请求时不再询问
+        #[arg(long)]
+        deny: bool,
+    },
+    /// 清除插件的存储数据（键值数据、数据文件夹和缓存文件夹），需先关闭应用
+    Wipe {
+        /// 插件 id 或名称
+        plugin: String,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+    /// 服务器端口
+    #[arg(short, long)]
+    pub port: Option<u16>,
+    /// 无窗口模式：只启动服务器和插件，bridge 命令通过 HTTP/WebSocket 访问
+    #[arg(long)]
+    pub headless: bool,
+}
+
+/// 各子命令共用的参数，指定时覆盖配置文件中的对应项
+#[derive(Args, Debug, Clone)]
+pub struct CommonArgs {
+    /// 项目配置文件路径，默认为当前目录下的 start.toml
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// 插件目录
+    #[arg(long)]
+    pub plugin_dir: Option<PathBuf>,
+    /// 日志级别
+    #[arg(long)]
+    pub log_level: Option<log::LevelFilter>,
+}
+
+impl Cli {
+    /// 解析命令行；未指定子命令时等同于 `run`
+    pub fn command() -> Command {
+        let cli = Self::parse();
+        cli.command.unwrap_or(Command::Run(cli.run))
+    }
+}
+
+impl Command {
+    pub fn common(&self) -> &CommonArgs {
+        match self {
+            Command::Run(args) => &args.common,
+            Command::Plugins { common, .. } | Command::Call { common, .. } => common,
+        }
+    }
+
+    /// 命令行对配置的覆盖项
+    pub fn overrides(&self) -> toml::Table {
+        let mut table = toml::Table::new();
+        let common = self.common();
+        if let Some(dir) = &common.plugin_dir {
+            let dir = toml::Value::String(dir.to_string_lossy().to_string());
+            set_path(&mut table, "plugins.dirs", toml::Value::Array(vec![dir]));
+        }
+        if let Some(level) = common.log_level {
+            set_path(&mut table, "log.level", level.to_string().into());
+        }
+        if let Command::Run(RunArgs {
+            port: Some(port), ..
+        }) = self
+        {
+            set_path(&mut table, "server.port", i64::from(*port).into());
+        }
+        table
+    }
+}
+
+// ---------- 子命令执行 ----------
+
+/// 执行 `plugins` 子命令
+pub async fn plugins(state: &AppState, command: PluginsCommand) -> Result<()> {
+    let plugin_dir = state.config().plugin_dir();
+    match command {
+        PluginsCommand::List => {
+            // 只读取注册表，不加载插件
+            for entry in state.registry.entries() {
+                let info = entry.info;
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    entry.id,
+                    info.name,
+                    info.version,
+                    info.libfile,
+                    if entry.enabled { "enabled" } else { "disabled" }
+                );
+            }
+        }
+        PluginsCommand::Discover => {
+            for plugin in state.discover_plugins().await {
+                let root = serde_json::to_value(plugin.root)?;
+                let status = serde_json::to_value(plugin.status)?;
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    plugin.id.as_deref().unwrap_or("-"),
+                    plugin.name.as_deref().unwrap_or("-"),
+                    plugin.version.as_deref().unwrap_or("-"),
+                    root.as_str().unwrap_or_default(),
+                    status.as_str().unwrap_or_default(),
+                    plugin.path,
+                    plugin.error.unwrap_or_default()
+                );
+            }
+        }
+        PluginsCommand::Register { manifest } => {
+            state.restore_plugins().await;
+            let id = state
+                .register_plugin(manifest.to_string_lossy().to_string())
+                .await
+                .map_err(to_err)?;
+            println!("{id}");
+        }
+        PluginsCommand::Scan { dir } => {
+            let dir = dir.unwrap_or(plugin_dir);
+            let report = state
+                .scan(dir.to_string_lossy().to_string())
+                .await
+                .map_err(to_err)?;
+            for entry in report.entries {
+                let outcome = serde_json::to_value(entry.outcome)?;
+                println!(
+                    "{}\t{}\t{}ms\t{}\t{}",
+                    entry.id.as_deref().unwrap_or("-"),
+                    outcome.as_str().unwrap_or_default(),
+                    entry.millis,
+                    entry.path,
+                    entry.error.unwrap_or_default()
+                );
+            }
+        }
+        PluginsCommand::Install { path } if is_package(&path) => {
+            state.restore_plugins().await;
+            let id = state.install_package(&path).await?;
+            println!("{id}");
+        }
+        PluginsCommand::Install { path } => {
+            let id = install(state, &path, &plugin_dir).await?;
+            println!("{id}");
+        }
+        PluginsCommand::Remove { plugin, force } => {
+            state.restore_plugins().await;
+            remove(state, &plugin, force).await?
+        }
+        PluginsCommand::Sign { manifest, key } => {
+            let publisher = sign(&manifest, &key)?;
+            println!("{publisher}");
+        }
+        PluginsCommand::Enable { plugin } => {
+            state.restore_plugins().await;
+            let id = resolve_registered(state, &plugin)?;
+            state.enable_plugin(id.clone()).await.map_err(to_err)?;
+            println!("{id}");
+        }
+        PluginsCommand::Disable { plugin, force } => {
+            state.restore_plugins().await;
+            let id = resolve_registered(state, &plugin)?;
+            if force {
+                for dependent in state.unload_dependents(&PluginId(id.clone().into())) {
+                    println!("{dependent}\tdisabled");
+                }
+            }
+            state.disable_plugin(id.clone()).await.map_err(to_err)?;
+            println!("{id}");
+        }
+        PluginsCommand::Grant { plugin, path, deny } => {
+            state.restore_plugins().await;
+            let id = resolve_registered(state, &plugin)?;
+            let path = state.set_path_grant(&id, &path, !deny)?;
+            println!("{id}\t{}", path.display());
+        }
+        PluginsCommand::Wipe { plugin } => {
+            let freed = state.wipe_plugin_data(&plugin).await?;
+            println!("{plugin}\t{freed}");
+        }
+    }
+    Ok(())
+}
+
+/// 执行 `call` 子命令：加载插件目录后调用插件方法
+pub async fn call(state: &AppState, plugin: &str, method: &str, params: &str) -> Result<()> {
+    let params: Value = serde_json::from_str(params)?;
+    state.restore_plugins().await;
+    let plugin_id = resolve(state, plugin).ok_or_else(|| anyhow!("plugin not found: {plugin}"))?;
+    let result = state
+        .call_plugin(&plugin_id, method, params)
+        .await
+        .map_err(to_err)?;
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(())
+}
+
+/// 复制插件到插件目录下以插件名命名的文件夹，并加载一次以验证
+async fn install(state: &AppState, path: &Path, plugin_dir: &Path) -> Result<String> {
+    let manifest = if path.is_dir() {
+        find_manifests(path, &state.config().plugins.discovery)
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no plugin manifest found in {path:?}"))?
+    } else {
+        path.to_path_buf()
+    };
+    let info = read_manifest(&manifest).await.map_err(to_err)?;
+    let src = manifest.parent().unwrap_or_else(|| Path::new("."));
+    let dest = plugin_dir.join(&info.name);
+    if dest.exists() {
+        return Err(anyhow!(
+            "plugin {} is already installed at {dest:?}",
+            info.name
+        ));
+    }
+    copy_dir(src, &dest)?;
+
+    let file_name = manifest.file_name().unwrap_or_default();
+    match load_plugin_from_json(state, &dest.join(file_name)).await {
+        Ok(id) => {
+            state.registry.set_installed(&id, &dest);
+            debug!("Installed plugin {} to {dest:?}", info.name);
+            Ok(id)
+        }
+        Err(e) => {
+            std::fs::remove_dir_all(&dest)?;
+            Err(anyhow!("plugin {} is invalid: {e}", info.name))
+        }
+    }
+}
+
+/// 为描述文件中的库文件签名并写回；插件包描述文件按平台分别签名，返回发布者公钥
+fn sign(manifest: &Path, key: &Path) -> Result<String> {
+    let seed = STANDARD.decode(std::fs::read_to_string(key)?.trim())?;
+    let seed: [u8; 32] = seed
+        .try_into()
+        .map_err(|_| anyhow!("key must be 32 bytes"))?;
+    let mut json: serde_json::Map<String, Value> =
+        serde_json::from_str(&std::fs::read_to_string(manifest)?)?;
+    let parent = manifest.parent().unwrap_or_else(|| Path::new("."));
+    // 签名覆盖描述文件的全部字段，与安装后（见 [PluginInfo::canonicalize_by_parent]）读到的一致
+    let info = |libfile: &Value| -> Result<PluginInfo> {
+        let mut fields = json.clone();
+        fields.insert("libfile".to_string(), libfile.clone());
+        fields
+            .entry("uiurl")
+            .or_insert_with(|| Value::String(String::new()));
+        let info: PluginInfo = serde_json::from_value(Value::Object(fields))?;
+        Ok(info.canonicalize_by_parent(parent))
+    };
+
+    let mut publisher = String::new();
+    if let Some(Value::Object(libs)) = json.get("libs") {
+        let mut signatures = serde_json::Map::new();
+        for (target, libfile) in libs {
+            let (key, signature) = pluginmanager::sign(&info(libfile)?, &seed)?;
+            signatures.insert(target.clone(), signature.into());
+            publisher = key;
+        }
+        json.insert("signatures".to_string(), signatures.into());
+    } else {
+        let libfile = json.get("libfile").cloned().unwrap_or_default();
+        let (key, signature) = pluginmanager::sign(&info(&libfile)?, &seed)?;
+        json.insert("signature".to_string(), signature.into());
+        publisher = key;
+    }
+    json.insert("publisher".to_string(), publisher.clone().into());
+    std::fs::write(manifest, serde_json::to_string_pretty(&json)?)?;
+    Ok(publisher)
+}
+
+/// 插件包为 json 之外的文件
+fn is_package(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext != "json")
+}
+
+/// 从插件目录中删除插件
+/// 插件文件夹可能与其他插件共用，只删除注册表中记录的安装时创建的文件夹
+async fn remove(state: &AppState, plugin: &str, force: bool) -> Result<()> {
+    let id = resolve_registered(state, plugin)?;
+    let installed = state.registry.get(&id).and_then(|e| e.installed);
+    let unloaded = if force {
+        state.force_unload_plugin(id.clone()).await
+    } else {
+        state.unload_plugin(id.clone()).await.map(|_| Vec::new())
+    }
+    .map_err(to_err)?;
+    for dependent in unloaded.iter().filter(|u| **u != id) {
+        println!("{dependent}\tdisabled");
+    }
+    match installed {
+        Some(dir) => std::fs::remove_dir_all(&dir)?,
+        None => debug!("plugin {id} was not installed, keep its files"),
+    }
+    println!("{id}");
+    Ok(())
+}
+
+/// 通过插件 id 或名称查找已加载的插件
+fn resolve(state: &AppState, plugin: &str) -> Option<PluginId> {
+    state
+        .pm
+        .list()
+        .into_iter()
+        .find(|(id, info)| id.0.as_ref() == plugin || info.name == plugin)
+        .map(|(id, _)| id)
+}
+
+/// 通过插件 id 或名称查找已登记的插件（含已禁用的插件）
+fn resolve_registered(state: &AppState, plugin: &str) -> Result<String> {
+    state
+        .registry
+        .entries()
+        .into_iter()
+        .find(|e| e.id == plugin || e.info.name == plugin)
+        .map(|e| e.id)
+        .ok_or_else(|| anyhow!("plugin not found: {plugin}"))
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> Result<()> {
+    for entry in WalkDir::new(src).into_iter().filter_map(|e| e.ok()) {
+        let target = dest.join(entry.path().strip_prefix(src)?);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+fn to_err(e: Box<dyn std::error::Error + Send + Sync>) -> anyhow::Error {
+    anyhow!("{e}")
+}
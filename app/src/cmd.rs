@@ -1,25 +1,70 @@
 use crate::AppState;
-use host_pluginmanager::Scan;
-use libcommon::{Result, debug, warn};
-use pluginmanager::PluginId;
+use host_pluginmanager::{DisablePlugin, EnablePlugin, RegisterPlugin, Scan};
+use libcommon::Result;
+use pluginmanager::{PluginError, PluginId, plugin::Binary};
 use serde::{Deserialize, Serialize};
-use window::{WindowState, bridge};
+use window::{CommandError, Reply, Source, WindowId, WindowState, bridge};
 
+/// 已加载的插件及已禁用的插件
 #[bridge]
 pub async fn listplugins(WindowState(state): WindowState<AppState>) -> Result<Vec<PluginInfo>> {
     let list = state.pm.list_full_info();
-    Ok(list.iter().map(From::from).collect())
+    let mut result: Vec<PluginInfo> = list
+        .iter()
+        .map(|item| {
+            let mut info = PluginInfo::from(item);
+            info.url = state.server.plugin_url(&info.id);
+            info
+        })
+        .collect();
+    let disabled = state.registry.entries().into_iter().filter(|e| !e.enabled);
+    for entry in disabled {
+        let mut info = PluginInfo::from(&(PluginId(entry.id.into()), entry.info));
+        info.status = PluginStatus::Disabled;
+        result.push(info);
+    }
+    Ok(result)
 }
 
 #[bridge]
-pub async fn scan(dir: String, WindowState(state): WindowState<AppState>) -> Vec<String> {
-    match state.scan(dir).await {
-        Ok(r) => r,
-        Err(e) => {
-            warn!("scan error: {e}");
-            Vec::new()
-        }
-    }
+pub async fn enableplugin(
+    pluginid: String,
+    WindowState(state): WindowState<AppState>,
+) -> Result<(), String> {
+    state
+        .enable_plugin(pluginid)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[bridge]
+pub async fn disableplugin(
+    pluginid: String,
+    WindowState(state): WindowState<AppState>,
+) -> Result<(), String> {
+    state
+        .disable_plugin(pluginid)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 扫描并加载文件夹中的插件，返回每个描述文件的结果（路径、结果、错误及耗时），
+/// 文件夹不存在时返回错误
+#[bridge]
+pub async fn scan(
+    dir: String,
+    WindowState(state): WindowState<AppState>,
+) -> Result<serde_json::Value, String> {
+    let report = state.scan(dir).await.map_err(|e| e.to_string())?;
+    serde_json::to_value(report).map_err(|e| e.to_string())
+}
+
+/// 启动时恢复插件的结果：按注册表恢复、新发现、已变化、已缺失、已禁用和加载失败的插件
+#[bridge]
+pub async fn restorereport(
+    WindowState(state): WindowState<AppState>,
+) -> Result<serde_json::Value, String> {
+    serde_json::to_value(state.restore_report()).map_err(|e| e.to_string())
 }
 
 #[bridge]
@@ -28,8 +73,16 @@ pub async fn callplugin(
     method: String,
     params: serde_json::Value,
     pm: WindowState<AppState>,
-) -> Result<serde_json::Value, String> {
-    _call_plugin_method(pluginid, method, params, pm).await
+) -> Result<Reply, CommandError> {
+    let value = _call_plugin_method(pluginid, method, params, pm).await?;
+    // 插件返回的二进制数据通过二进制通道发送
+    Ok(match Binary::from_value(&value) {
+        Some(bin) => Reply::Binary {
+            mime: bin.mime,
+            data: bin.data,
+        },
+        None => Reply::Json(value),
+    })
 }
 
 async fn _call_plugin_method(
@@ -37,24 +90,158 @@ async fn _call_plugin_method(
     method: String,
     params: serde_json::Value,
     WindowState(state): WindowState<AppState>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, CommandError> {
     let plugin_id = PluginId(pluginid.into());
-    debug!("call plugin({plugin_id}) method: {method}, params: {params:?}");
-    let input = serde_json::json!({ "method": method, "params": params});
     let result = state
-        .pm
-        .call(&plugin_id, input, state.as_ref())
+        .call_plugin(&plugin_id, &method, params)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(
+            |e| match e.downcast_ref::<PluginError>().and_then(PluginError::code) {
+                Some(code) => CommandError::new(code, e.to_string()),
+                None => CommandError::from(e.to_string()),
+            },
+        )?;
     Ok(result)
 }
 
+/// 列出项目、用户和系统插件目录中的插件（路径、来源、状态等），不加载
+#[bridge]
+pub async fn discoverplugins(
+    WindowState(state): WindowState<AppState>,
+) -> Result<serde_json::Value, String> {
+    serde_json::to_value(state.discover_plugins().await).map_err(|e| e.to_string())
+}
+
+/// 登记并加载 `discoverplugins` 列出的插件（参数为描述文件路径），返回插件 id；
+/// 之后启动时按注册表恢复
+#[bridge]
+pub async fn registerplugin(
+    path: String,
+    WindowState(state): WindowState<AppState>,
+) -> Result<String, String> {
+    state.register_plugin(path).await.map_err(|e| e.to_string())
+}
+
+/// 插件调用和 bridge 命令的统计：调用数、失败数、进行中的调用数及耗时分布（`buckets` 为各区间上限，秒）
+#[bridge]
+pub async fn pluginmetrics(WindowState(state): WindowState<AppState>) -> Result<serde_json::Value> {
+    Ok(state.metrics())
+}
+
+/// 当前生效的配置
+#[bridge]
+pub async fn getconfig(WindowState(state): WindowState<AppState>) -> Result<serde_json::Value> {
+    Ok(serde_json::to_value(state.config())?)
+}
+
+/// 修改配置项（如 `window.width`）并保存到用户配置文件，返回修改后的配置；
+/// 签名信任设置（`trust`）只能在配置文件中修改
+#[bridge]
+pub async fn setconfig(
+    key: String,
+    value: serde_json::Value,
+    WindowState(state): WindowState<AppState>,
+) -> Result<serde_json::Value, String> {
+    if key == "trust" || key.starts_with("trust.") {
+        return Err(format!("{key} can only be changed in the config file"));
+    }
+    let config = state.set_config(&key, value).map_err(|e| e.to_string())?;
+    serde_json::to_value(config).map_err(|e| e.to_string())
+}
+
+/// 回复插件权限确认请求（见 `permission` 事件），只允许权限确认窗口调用
+#[bridge]
+pub async fn answerpermission(
+    id: String,
+    granted: bool,
+    WindowState(state): WindowState<AppState>,
+) -> Result<bool> {
+    Ok(state.prompts.answer(&id, granted))
+}
+
+/// 分页读取插件日志：返回序号大于 `after` 且不低于 `level`（trace/debug/info/warn/error）的至多 `limit` 条，
+/// 以返回的 `next` 作为下一次的 `after` 即可持续获取新日志
+#[bridge]
+pub async fn pluginlogs(
+    pluginid: String,
+    after: u64,
+    limit: u32,
+    level: String,
+    WindowState(state): WindowState<AppState>,
+) -> Result<serde_json::Value, String> {
+    let level = serde_json::from_value(serde_json::Value::String(level.to_lowercase()))
+        .map_err(|e| format!("invalid log level: {e}"))?;
+    let page = state.logs.page(&pluginid, after, limit as usize, level);
+    serde_json::to_value(page).map_err(|e| e.to_string())
+}
+
+/// 清除插件的存储数据（插件 id 或名称），返回释放的键值容量（字节）
+#[bridge]
+pub async fn wipeplugindata(
+    pluginid: String,
+    WindowState(state): WindowState<AppState>,
+) -> Result<u64, String> {
+    state
+        .wipe_plugin_data(&pluginid)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 订阅事件总线上的主题（如 `plugin.*`），事件通过 `event` 事件只送达订阅的窗口
+#[bridge]
+pub async fn subscribeevent(
+    topic: String,
+    WindowState(state): WindowState<AppState>,
+) -> Result<(), String> {
+    state.subscribe_ui(source_window()?, topic);
+    Ok(())
+}
+
+#[bridge]
+pub async fn unsubscribeevent(
+    topic: String,
+    WindowState(state): WindowState<AppState>,
+) -> Result<(), String> {
+    state.unsubscribe_ui(source_window()?, &topic);
+    Ok(())
+}
+
+/// 调用命令的窗口，事件只能发送给窗口
+fn source_window() -> Result<WindowId, String> {
+    match Source::current() {
+        Some(Source::Window(window)) => Ok(window),
+        _ => Err("events can only be subscribed from a window".to_string()),
+    }
+}
+
+/// 向事件总线发布事件，宿主主题（`plugin.*`、`config.*`、`window.*`）不能由前端发布
+#[bridge]
+pub async fn publishevent(
+    topic: String,
+    payload: serde_json::Value,
+    WindowState(state): WindowState<AppState>,
+) -> Result<(), String> {
+    state.publish_ui(topic, payload)
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PluginInfo {
     id: String,
     name: String,
     version: String,
     path: String,
+    /// 插件 UI 的同源地址（文件夹或 dev server 均经由宿主服务器访问），禁用时为空
+    url: String,
+    status: PluginStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginStatus {
+    /// 已加载
+    Enabled,
+    /// 已登记但未加载
+    Disabled,
 }
 
 impl From<&(PluginId, pluginmanager::PluginInfo)> for PluginInfo {
@@ -64,6 +251,8 @@ impl From<&(PluginId, pluginmanager::PluginInfo)> for PluginInfo {
             name: info.name.to_string(),
             version: info.version.to_string(),
             path: info.uiurl.to_string(),
+            url: String::new(),
+            status: PluginStatus::Enabled,
         }
     }
 }
@@ -0,0 +1,444 @@
+use crate::AppState;
+use anyhow::{Result, anyhow};
+use host_config::HostConfig;
+use host_pluginmanager::PluginRoot;
+use libcommon::{debug, warn};
+use pluginmanager::{
+    CallLimits, SignaturePolicy,
+    plugin::{PluginResult, Value, async_trait},
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 应用名，用于平台配置目录下的子文件夹
+pub const APP_NAME: &str = "start";
+/// 用户配置文件名（位于平台配置目录）
+pub const USER_CONFIG_FILE: &str = "config.toml";
+/// 项目配置文件名（位于当前工作目录）
+pub const PROJECT_CONFIG_FILE: &str = "start.toml";
+/// 插件注册表文件名（位于平台数据目录）
+pub const REGISTRY_FILE: &str = "plugins.json";
+/// bridge HTTP 接口令牌文件名（位于平台数据目录），每次启动时重写
+pub const TOKEN_FILE: &str = "bridge.token";
+/// 环境变量前缀，层级以`__`分隔，如 `START_SERVER__PORT=3031`
+pub const ENV_PREFIX: &str = "START_";
+
+/// 应用配置
+///
+/// 按 内置默认值 < 用户配置 < 项目配置 < 环境变量 < 命令行 的顺序逐层覆盖
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub plugins: PluginsConfig,
+    pub log: LogConfig,
+    pub window: WindowConfig,
+    pub trust: TrustConfig,
+    pub storage: StorageConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub port: u16,
+    /// 监听地址，默认只接受本机连接
+    pub bind: String,
+    /// 是否在 `/metrics` 以 Prometheus 文本格式提供调用统计
+    pub metrics: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginsConfig {
+    /// 项目插件目录，第一个目录同时作为插件安装目录
+    pub dirs: Vec<PathBuf>,
+    /// 用户插件目录，默认位于平台数据目录
+    pub user_dir: Option<PathBuf>,
+    /// 系统插件目录，默认为 `/usr/share/start/plugins`（Windows 为 `%ProgramData%\start\plugins`）
+    pub system_dir: Option<PathBuf>,
+    /// 插件注册表文件，默认位于平台数据目录
+    pub registry: Option<PathBuf>,
+    pub discovery: DiscoveryConfig,
+    /// 插件调用的并发限制，见 [CallLimits]
+    pub limits: CallLimits,
+}
+
+/// 在插件目录中查找插件描述文件的规则，见 [find_manifests](crate::discovery::find_manifests)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryConfig {
+    /// 插件描述文件名；其他 json 文件需以 `$schema` 标记（见 [MANIFEST_SCHEMA](crate::discovery::MANIFEST_SCHEMA)）
+    pub manifests: Vec<String>,
+    /// 查找的最大深度，插件目录本身为 0
+    pub depth: usize,
+    /// 跳过的文件和文件夹（glob，匹配名称或相对于插件目录的路径），以`.`开头的始终跳过
+    pub ignore: Vec<String>,
+    /// 是否进入符号链接指向的文件夹
+    pub follow_symlinks: bool,
+}
+
+/// 插件签名信任设置
+///
+/// 发布者公钥由 `plugins sign` 输出，在配置文件中添加后即信任该发布者签名的插件：
+///
+/// ```toml
+/// [trust]
+/// policy = "enforce"
+/// keys = ["<base64 公钥>"]
+/// ```
+///
+/// 也可通过环境变量设置，如 `START_TRUST__POLICY=enforce`、`START_TRUST__KEYS='["<base64 公钥>"]'`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrustConfig {
+    /// 签名校验策略：enforce、warn（默认）、off
+    pub policy: SignaturePolicy,
+    /// 受信任的发布者公钥（base64）
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    pub level: String,
+    /// 每个插件在内存中保留的日志条数
+    pub buffer: usize,
+    /// 是否为每个插件写入单独的日志文件
+    pub plugin_files: bool,
+    /// 插件日志文件夹，默认位于平台数据目录
+    pub dir: Option<PathBuf>,
+}
+
+/// 插件数据存储设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// 存储文件夹，默认位于平台数据目录
+    pub dir: Option<PathBuf>,
+    /// 插件缓存文件夹，默认位于平台缓存目录
+    pub cache: Option<PathBuf>,
+    /// 每个插件键值数据的容量上限（字节，按键和值的长度计算）
+    pub quota: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: f64,
+    pub height: f64,
+    pub decorations: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 3030,
+            bind: "127.0.0.1".to_string(),
+            metrics: false,
+        }
+    }
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            dirs: vec![PathBuf::from("dist")],
+            user_dir: None,
+            system_dir: None,
+            registry: None,
+            discovery: DiscoveryConfig::default(),
+            limits: CallLimits::default(),
+        }
+    }
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            manifests: vec![crate::discovery::MANIFEST_FILE.to_string()],
+            depth: 4,
+            ignore: vec!["node_modules".to_string(), "target".to_string()],
+            follow_symlinks: false,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "trace".to_string(),
+            buffer: 1000,
+            plugin_files: false,
+            dir: None,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            cache: None,
+            quota: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "main".to_string(),
+            width: 1080.0,
+            height: 720.0,
+            decorations: false,
+        }
+    }
+}
+
+impl AppConfig {
+    /// 逐层加载配置；`project` 为空时使用当前工作目录下的 [PROJECT_CONFIG_FILE]，
+    /// `overrides` 为命令行覆盖项
+    pub fn load(project: Option<&Path>, overrides: toml::Table) -> Result<Self> {
+        let mut merged = toml::Table::try_from(Self::default())?;
+        if let Some(user) = user_config_path()
+            && user.is_file()
+        {
+            merge(&mut merged, read_table(&user)?);
+        }
+        match project {
+            Some(path) if !path.is_file() => {
+                return Err(anyhow!("config file not found: {path:?}"));
+            }
+            Some(path) => merge(&mut merged, read_table(path)?),
+            None => {
+                let path = Path::new(PROJECT_CONFIG_FILE);
+                if path.is_file() {
+                    merge(&mut merged, read_table(path)?);
+                }
+            }
+        }
+        merge(&mut merged, env_overrides(std::env::vars()));
+        merge(&mut merged, overrides);
+        let config: Self = toml::Value::Table(merged).try_into()?;
+        debug!("config: {config:?}");
+        Ok(config)
+    }
+
+    /// 插件安装目录
+    pub fn plugin_dir(&self) -> PathBuf {
+        self.plugins
+            .dirs
+            .first()
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from("dist"))
+    }
+
+    /// 依次为项目、用户和系统插件目录；同一插件出现在多个目录中时以靠前的为准
+    pub fn plugin_roots(&self) -> Vec<(PluginRoot, PathBuf)> {
+        let mut roots: Vec<(PluginRoot, PathBuf)> = self
+            .plugins
+            .dirs
+            .iter()
+            .map(|dir| (PluginRoot::Project, dir.clone()))
+            .collect();
+        let user = self
+            .plugins
+            .user_dir
+            .clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join(APP_NAME).join("plugins")));
+        roots.extend(user.map(|dir| (PluginRoot::User, dir)));
+        let system = self.plugins.system_dir.clone().or_else(system_plugin_dir);
+        roots.extend(system.map(|dir| (PluginRoot::System, dir)));
+        roots
+    }
+
+    /// 插件注册表文件
+    pub fn registry_path(&self) -> Option<PathBuf> {
+        self.plugins
+            .registry
+            .clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join(APP_NAME).join(REGISTRY_FILE)))
+    }
+
+    /// bridge HTTP 接口令牌文件
+    pub fn token_path(&self) -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(APP_NAME).join(TOKEN_FILE))
+    }
+
+    /// 插件日志文件夹，未开启插件日志文件时返回 None
+    pub fn plugin_log_dir(&self) -> Option<PathBuf> {
+        if !self.log.plugin_files {
+            return None;
+        }
+        self.log
+            .dir
+            .clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join(APP_NAME).join("logs")))
+    }
+
+    /// 插件数据存储文件夹
+    pub fn storage_dir(&self) -> PathBuf {
+        self.storage
+            .dir
+            .clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join(APP_NAME).join("storage")))
+            .unwrap_or_else(|| PathBuf::from("storage"))
+    }
+
+    /// 插件缓存文件夹
+    pub fn cache_dir(&self) -> PathBuf {
+        self.storage
+            .cache
+            .clone()
+            .or_else(|| dirs::cache_dir().map(|dir| dir.join(APP_NAME).join("plugins")))
+            .unwrap_or_else(|| self.storage_dir().join("cache"))
+    }
+
+    pub fn log_level(&self) -> log::LevelFilter {
+        self.log.level.parse().unwrap_or_else(|_| {
+            warn!("invalid log level: {}", self.log.level);
+            log::LevelFilter::Trace
+        })
+    }
+
+    /// 按路径读取配置项，路径为空时返回全部配置
+    pub fn get(&self, key: &str) -> Option<Value> {
+        let value = serde_json::to_value(self).ok()?;
+        if key.is_empty() {
+            return Some(value);
+        }
+        value
+            .pointer(&format!("/{}", key.replace('.', "/")))
+            .cloned()
+    }
+
+    /// 按路径修改配置项，修改后的配置必须仍然有效
+    pub fn set(&mut self, key: &str, value: Value) -> Result<()> {
+        let mut table = toml::Table::try_from(&*self)?;
+        set_path(&mut table, key, toml::Value::try_from(value)?);
+        *self = toml::Value::Table(table).try_into()?;
+        Ok(())
+    }
+}
+
+/// 默认的系统插件目录
+fn system_plugin_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("ProgramData").map(|dir| PathBuf::from(dir).join(APP_NAME).join("plugins"))
+    } else if cfg!(target_os = "macos") {
+        Some(
+            PathBuf::from("/Library/Application Support")
+                .join(APP_NAME)
+                .join("plugins"),
+        )
+    } else {
+        Some(PathBuf::from("/usr/share").join(APP_NAME).join("plugins"))
+    }
+}
+
+/// 平台配置目录下的用户配置文件
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_NAME).join(USER_CONFIG_FILE))
+}
+
+/// 将配置项写入用户配置文件
+pub fn save_user(key: &str, value: Value) -> Result<()> {
+    let path = user_config_path().ok_or_else(|| anyhow!("config dir not found"))?;
+    let mut table = if path.is_file() {
+        read_table(&path)?
+    } else {
+        toml::Table::new()
+    };
+    set_path(&mut table, key, toml::Value::try_from(value)?);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, toml::to_string_pretty(&table)?)?;
+    debug!("saved config {key} to {path:?}");
+    Ok(())
+}
+
+/// 按以`.`分隔的路径设置值，中间层级不存在时创建
+pub fn set_path(table: &mut toml::Table, key: &str, value: toml::Value) {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap_or_default();
+    let mut curr = table;
+    for part in parts {
+        let entry = curr
+            .entry(part.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        curr = match entry {
+            toml::Value::Table(t) => t,
+            _ => unreachable!(),
+        };
+    }
+    curr.insert(last.to_string(), value);
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+    let content = std::fs::read_to_string(path)?;
+    toml::from_str(&content).map_err(|e| anyhow!("invalid config file {path:?}: {e}"))
+}
+
+/// 深度合并，`from` 中的值覆盖 `into` 中的同名项
+fn merge(into: &mut toml::Table, from: toml::Table) {
+    for (key, value) in from {
+        match (into.get_mut(&key), value) {
+            (Some(toml::Value::Table(dst)), toml::Value::Table(src)) => merge(dst, src),
+            (_, value) => {
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+/// 从环境变量中读取覆盖项，值按 TOML 解析，解析失败时视为字符串
+fn env_overrides(vars: impl Iterator<Item = (String, String)>) -> toml::Table {
+    let mut table = toml::Table::new();
+    for (name, raw) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let key = key.to_lowercase().replace("__", ".");
+        let value = toml::from_str::<toml::Table>(&format!("v = {raw}"))
+            .ok()
+            .and_then(|mut t| t.remove("v"))
+            .unwrap_or(toml::Value::String(raw));
+        set_path(&mut table, &key, value);
+    }
+    table
+}
+
+impl AppState {
+    /// 当前生效的配置
+    pub fn config(&self) -> AppConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 修改配置项并写入用户配置文件，部分配置（如端口）重启后生效
+    pub fn set_config(&self, key: &str, value: Value) -> Result<AppConfig> {
+        let mut config = self.config();
+        config.set(key, value.clone())?;
+        save_user(key, value.clone())?;
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config.clone();
+        let payload = serde_json::json!({ "key": key, "value": value });
+        self.publish_host(host_events::TOPIC_CONFIG_CHANGED, payload);
+        Ok(config)
+    }
+}
+
+#[async_trait]
+impl HostConfig for AppState {
+    async fn get_config(&self, arg: String) -> PluginResult<Value> {
+        self.config()
+            .get(&arg)
+            .ok_or_else(|| format!("config not found: {arg}").into())
+    }
+}
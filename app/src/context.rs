@@ -1,61 +1,202 @@
 use std::path::Path;
 
-use host_pluginmanager::HostPM;
-use libcommon::{debug, trace, warn};
+use host_call::HostCall;
+use host_events::{
+    TOPIC_PLUGIN_DISABLED, TOPIC_PLUGIN_ENABLED, TOPIC_PLUGIN_LOADED, TOPIC_PLUGIN_UNLOADED,
+};
+use host_pluginmanager::{DiscoveredPlugin, HostPM, ScanReport};
+use libcommon::{debug, trace};
 use pluginmanager::{
-    PluginError, PluginId,
-    plugin::{self, Context, PluginResult, async_trait},
+    Caller, PluginId,
+    plugin::{self, Context, LogRecord, PluginResult, async_trait},
 };
-use walkdir::WalkDir;
+use serde_json::json;
 
 use crate::AppState;
 
+/// 宿主上下文；插件调用时由 [PluginManager](pluginmanager::PluginManager) 包装，
+/// 可通过 [Caller::current] 得到调用方插件
 #[async_trait]
 impl Context for AppState {
-    fn log(&self, msg: &str) {
-        debug!("{msg}")
+    fn log_record(&self, record: LogRecord) {
+        match Caller::current() {
+            Some(caller) => self.logs.record(&caller, record),
+            None => debug!("{}", record.message),
+        }
     }
     async fn call_host(
         &self,
         cmd: &str,
         args: plugin::Value,
     ) -> plugin::PluginResult<plugin::Value> {
-        if let Some(value) = host_pluginmanager::try_dispatch_host_p_m(self, cmd, args).await {
+        // 宿主方法只经由插件的上下文调用；读取不到调用方时无法检查权限，一律拒绝
+        let caller = Caller::current().ok_or("host methods are only available to plugins")?;
+        self.check_permission(&caller, cmd).await?;
+        if let Some(value) =
+            host_pluginmanager::try_dispatch_host_p_m(self, cmd, args.clone()).await
+        {
+            return value;
+        }
+        if let Some(value) = host_config::try_dispatch_host_config(self, cmd, args.clone()).await {
+            return value;
+        }
+        if let Some(value) = host_storage::try_dispatch_host_storage(self, cmd, args.clone()).await
+        {
+            return value;
+        }
+        if let Some(value) = host_fs::try_dispatch_host_fs(self, cmd, args.clone()).await {
+            return value;
+        }
+        if let Some(value) = host_call::try_dispatch_host_call(self, cmd, args.clone()).await {
+            return value;
+        }
+        if let Some(value) = host_events::try_dispatch_host_events(self, cmd, args).await {
             return value;
         }
         Err("not implemented".into())
     }
 }
 
-fn map_err(e: PluginError) -> Box<dyn std::error::Error + Send + Sync> {
-    format!("Plugin error: {}", e).into()
+impl AppState {
+    /// 以宿主上下文调用插件方法
+    pub async fn call_plugin(
+        &self,
+        plugin_id: &PluginId,
+        method: &str,
+        params: plugin::Value,
+    ) -> PluginResult<plugin::Value> {
+        debug!("call plugin({plugin_id}) method: {method}, params: {params:?}");
+        let input = serde_json::json!({ "method": method, "params": params });
+        self.pm.call(plugin_id, input, self).await
+    }
+
+    /// 加载插件、挂载其 UI 并记录到注册表；已禁用的插件需先启用
+    pub(crate) async fn load(
+        &self,
+        info: pluginmanager::PluginInfo,
+        manifest: Option<&Path>,
+    ) -> PluginResult<String> {
+        let id = PluginId::from(&info).to_string();
+        if self.registry.get(&id).is_some_and(|e| !e.enabled) {
+            return Err(format!("plugin {id} is disabled").into());
+        }
+        let (uiurl, spa) = (info.uiurl.clone(), info.spa);
+        let pid = self.pm.load(info.clone())?;
+        self.server.add_plugin_route(&pid.to_string(), uiurl, spa);
+        let payload = json!({ "id": pid.to_string(), "name": info.name, "version": info.version });
+        self.registry.record(&pid.to_string(), info, manifest);
+        self.publish_host(TOPIC_PLUGIN_LOADED, payload);
+        Ok(pid.to_string())
+    }
+
+    /// 卸载插件、停止其文件监听和事件订阅并移除其 UI 路由，不修改注册表
+    pub(crate) fn unload(&self, pid: &PluginId) {
+        if self.pm.get(pid).is_some() {
+            self.publish_host(TOPIC_PLUGIN_UNLOADED, json!({ "id": pid.to_string() }));
+        }
+        self.pm.unload(pid);
+        self.watches.clear(pid);
+        self.events.remove(pid);
+        self.server.remove_plugin_route(&pid.to_string());
+    }
+
+    /// 有已加载的插件依赖该插件时返回错误
+    fn check_dependents(&self, pid: &PluginId) -> PluginResult<()> {
+        let names: Vec<String> = self
+            .pm
+            .dependents(pid)
+            .iter()
+            .filter_map(|id| self.pm.get(id))
+            .map(|info| info.name)
+            .collect();
+        if names.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "plugin {pid} is required by {}, unload them first or force it",
+            names.join(", ")
+        )
+        .into())
+    }
+
+    /// 直接或间接依赖该插件的已加载插件，依赖方排在被依赖方之前
+    fn dependents(&self, pid: &PluginId) -> Vec<PluginId> {
+        fn visit(
+            state: &AppState,
+            pid: &PluginId,
+            seen: &mut Vec<PluginId>,
+            result: &mut Vec<PluginId>,
+        ) {
+            for id in state.pm.dependents(pid) {
+                if !seen.contains(&id) {
+                    seen.push(id.clone());
+                    visit(state, &id, seen, result);
+                    result.push(id);
+                }
+            }
+        }
+        let (mut seen, mut result) = (vec![pid.clone()], Vec::new());
+        visit(self, pid, &mut seen, &mut result);
+        result
+    }
+
+    /// 卸载所有依赖该插件的插件并将其禁用（保留注册表记录），返回这些插件
+    pub(crate) fn unload_dependents(&self, pid: &PluginId) -> Vec<PluginId> {
+        let dependents = self.dependents(pid);
+        for id in &dependents {
+            debug!("Disabling plugin {id} which depends on {pid}");
+            self.unload(id);
+            self.registry.set_enabled(&id.to_string(), false);
+            self.publish_host(TOPIC_PLUGIN_DISABLED, json!({ "id": id.to_string() }));
+        }
+        dependents
+    }
 }
 
 #[async_trait]
 impl HostPM for AppState {
     async fn load_plugin(&self, arg: host_pluginmanager::PluginInfo) -> PluginResult<String> {
         let info = Into::<W<pluginmanager::PluginInfo>>::into(arg).0;
-        let uiurl = info.uiurl.clone();
-        let pid = self.pm.load(info).map_err(map_err)?;
-        self.server.add_plugin_route(&pid.to_string(), uiurl);
-        Ok(pid.to_string())
+        self.load(info, None).await
     }
 
     async fn unload_plugin(&self, arg: String) -> PluginResult<()> {
         let pid = PluginId(arg.into());
-        self.pm.unload(&pid);
-        self.server.remove_plugin_route(&pid.to_string());
+        self.check_dependents(&pid)?;
+        self.unload(&pid);
+        self.registry.remove(&pid.to_string());
+        self.logs.clear(&pid.to_string());
         debug!("Unloaded plugin: {pid}");
         Ok(())
     }
 
+    async fn force_unload_plugin(&self, arg: String) -> PluginResult<Vec<String>> {
+        let pid = PluginId(arg.clone().into());
+        let mut result: Vec<String> = self
+            .unload_dependents(&pid)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        self.unload_plugin(arg.clone()).await?;
+        result.push(arg);
+        Ok(result)
+    }
+
     async fn reload_plugin(
         &self,
         arg: (String, host_pluginmanager::PluginInfo),
     ) -> PluginResult<()> {
         debug!("Reloading plugin: {}", &arg.0);
-        self.unload_plugin(arg.0).await?;
-        self.load_plugin(arg.1).await?;
+        let info = Into::<W<pluginmanager::PluginInfo>>::into(arg.1).0;
+        // 替换前检查新插件的依赖，以及依赖原插件的插件的版本要求，失败时保留原插件
+        let old = PluginId(arg.0.clone().into());
+        self.pm.check_replace(&old, &info)?;
+        // 直接卸载而不移除注册表记录，以保留加载顺序和启用状态
+        self.unload(&old);
+        let pid = self.load(info, None).await?;
+        if pid != arg.0 {
+            self.registry.remove(&arg.0);
+        }
         Ok(())
     }
 
@@ -74,43 +215,115 @@ impl HostPM for AppState {
         Ok(result)
     }
 
-    async fn scan(&self, arg: String) -> PluginResult<Vec<String>> {
+    async fn enable_plugin(&self, arg: String) -> PluginResult<()> {
+        let entry = self
+            .registry
+            .get(&arg)
+            .ok_or_else(|| format!("plugin is not registered: {arg}"))?;
+        if self.pm.get(&PluginId(arg.clone().into())).is_some() {
+            self.registry.set_enabled(&arg, true);
+            return Ok(());
+        }
+        // load 只加载已启用的插件，先启用，加载失败时恢复原状态
+        self.registry.set_enabled(&arg, true);
+        let loaded = async {
+            let info = match &entry.manifest {
+                Some(manifest) => read_manifest(manifest).await?,
+                None => entry.info.clone(),
+            };
+            self.load(info, entry.manifest.as_deref()).await
+        }
+        .await;
+        if let Err(e) = loaded {
+            self.registry.set_enabled(&arg, entry.enabled);
+            return Err(e);
+        }
+        self.publish_host(TOPIC_PLUGIN_ENABLED, json!({ "id": arg }));
+        debug!("Enabled plugin: {arg}");
+        Ok(())
+    }
+
+    async fn disable_plugin(&self, arg: String) -> PluginResult<()> {
+        self.check_dependents(&PluginId(arg.clone().into()))?;
+        self.registry
+            .set_enabled(&arg, false)
+            .ok_or_else(|| format!("plugin is not registered: {arg}"))?;
+        self.unload(&PluginId(arg.clone().into()));
+        self.publish_host(TOPIC_PLUGIN_DISABLED, json!({ "id": arg }));
+        debug!("Disabled plugin: {arg}");
+        Ok(())
+    }
+
+    async fn install_plugin(&self, arg: String) -> PluginResult<String> {
+        Ok(self.install_package(Path::new(&arg)).await?)
+    }
+
+    async fn uninstall_plugin(&self, arg: String) -> PluginResult<()> {
+        Ok(self.uninstall_package(&arg).await?)
+    }
+
+    async fn scan(&self, arg: String) -> PluginResult<ScanReport> {
         let dir = Path::new(&arg);
-        if !dir.exists() {
+        if !dir.is_dir() {
             return Err(format!("directory does not exist: {arg}").into());
         }
-        let mut result = Vec::new();
-        for entry in WalkDir::new(dir)
-            .max_depth(2)
+        Ok(self.scan_dir(dir).await)
+    }
+
+    async fn discover(&self, _: ()) -> PluginResult<Vec<DiscoveredPlugin>> {
+        Ok(self.discover_plugins().await)
+    }
+
+    async fn register_plugin(&self, arg: String) -> PluginResult<String> {
+        self.register_discovered(Path::new(&arg)).await
+    }
+}
+
+#[async_trait]
+impl HostCall for AppState {
+    async fn call_plugin(
+        &self,
+        arg: (String, String, plugin::Value),
+    ) -> PluginResult<plugin::Value> {
+        let (target, method, params) = arg;
+        let caller = Caller::current().ok_or("call_plugin is only available to plugins")?;
+        let (id, info) = self
+            .pm
+            .list_full_info()
             .into_iter()
-            .filter_map(Result::ok)
-        {
-            let path = entry.path();
-            trace!("Scanning {path:?}");
-            if path.is_file()
-                && Some(mime_guess::mime::APPLICATION_JSON) == mime_guess::from_path(path).first()
-            {
-                match load_plugin_from_json(self, path).await {
-                    Ok(id) => {
-                        debug!("Loaded plugin from {path:?}: {id}");
-                        result.push(id);
-                    }
-                    Err(e) => warn!("Failed to load plugin from {path:?}: {e}"),
-                }
-            }
+            .find(|(id, info)| id.0.as_ref() == target || info.name == target)
+            .ok_or_else(|| format!("plugin not found: {target}"))?;
+        if !info.exports.iter().any(|e| e == "*" || *e == method) {
+            return Err(format!("plugin {} does not export {method}", info.name).into());
         }
-        Ok(result)
+        debug!("plugin {} call plugin({id}) method: {method}", caller.name);
+        let input = serde_json::json!({
+            "method": method,
+            "params": params,
+            "caller": {
+                "id": caller.plugin.to_string(),
+                "name": caller.name,
+                "version": caller.version,
+            },
+        });
+        self.pm.call(&id, input, self).await
     }
 }
 
-async fn load_plugin_from_json(pm: &AppState, path: &Path) -> PluginResult<String> {
-    trace!("try to loading plugin from {path:?}");
+/// 读取插件描述文件，并将其中的相对路径转换为绝对路径
+pub(crate) async fn read_manifest(path: &Path) -> PluginResult<pluginmanager::PluginInfo> {
     let content = tokio::fs::read_to_string(path).await?;
     let info: pluginmanager::PluginInfo = serde_json::from_str(&content)?;
     let parent = path.parent().unwrap_or_else(|| Path::new("."));
     let newinfo = info.canonicalize_by_parent(parent);
     trace!("{info:?} ==canonicalize==> {newinfo:?}");
-    pm.load_plugin(W(newinfo).into()).await
+    Ok(newinfo)
+}
+
+pub(crate) async fn load_plugin_from_json(pm: &AppState, path: &Path) -> PluginResult<String> {
+    trace!("try to loading plugin from {path:?}");
+    let info = read_manifest(path).await?;
+    pm.load(info, Some(path)).await
 }
 
 impl From<host_pluginmanager::PluginInfo> for W<pluginmanager::PluginInfo> {
@@ -120,6 +333,14 @@ impl From<host_pluginmanager::PluginInfo> for W<pluginmanager::PluginInfo> {
             version: value.version,
             libfile: value.libfile,
             uiurl: value.uiurl,
+            spa: value.spa,
+            permissions: value.permissions,
+            exports: value.exports,
+            serial: value.serial,
+            dependencies: value.dependencies,
+            host: value.host,
+            publisher: value.publisher,
+            signature: value.signature,
         };
         W(info)
     }
@@ -132,6 +353,14 @@ impl From<W<pluginmanager::PluginInfo>> for host_pluginmanager::PluginInfo {
             version: value.0.version,
             libfile: value.0.libfile,
             uiurl: value.0.uiurl,
+            spa: value.0.spa,
+            permissions: value.0.permissions,
+            exports: value.0.exports,
+            serial: value.0.serial,
+            dependencies: value.0.dependencies,
+            host: value.0.host,
+            publisher: value.0.publisher,
+            signature: value.0.signature,
         }
     }
 }
@@ -0,0 +1,305 @@
+use crate::{AppState, config::DiscoveryConfig, context::read_manifest};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use host_pluginmanager::{DiscoveredPlugin, DiscoveryStatus, ScanEntry, ScanOutcome, ScanReport};
+use libcommon::{debug, trace, warn};
+use pluginmanager::{PluginError, PluginId, PluginInfo, load_order, plugin::PluginResult};
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// 默认的插件描述文件名，安装插件包时也以此保存描述文件
+pub const MANIFEST_FILE: &str = "plugin.json";
+/// 文件名不在 [DiscoveryConfig::manifests] 中的 json 文件，需在 `$schema` 中包含该标记才视为插件描述文件，
+/// 如 `"$schema": "https://example.com/start-plugin.schema.json"`
+pub const MANIFEST_SCHEMA: &str = "start-plugin";
+/// 检查 `$schema` 标记时读取的文件大小上限
+const MAX_MARKER_FILE: u64 = 1024 * 1024;
+
+/// 查找插件目录中的插件描述文件
+///
+/// 包含描述文件的子文件夹视为插件文件夹，不再进入其下级（如 UI 资源）；
+/// 插件目录本身可以直接包含多个描述文件
+pub(crate) fn find_manifests(dir: &Path, config: &DiscoveryConfig) -> Vec<PathBuf> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in &config.ignore {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("invalid ignore pattern {pattern}: {e}"),
+        }
+    }
+    let ignore = builder.build().unwrap_or_else(|e| {
+        warn!("invalid ignore patterns: {e}");
+        GlobSet::empty()
+    });
+    let mut walker = Walker {
+        root: dir,
+        config,
+        ignore,
+        visited: Vec::new(),
+        found: Vec::new(),
+    };
+    walker.walk(dir, 0);
+    walker.found
+}
+
+struct Walker<'a> {
+    root: &'a Path,
+    config: &'a DiscoveryConfig,
+    ignore: GlobSet,
+    /// 已进入的文件夹的真实路径，跟随符号链接时避免循环
+    visited: Vec<PathBuf>,
+    found: Vec<PathBuf>,
+}
+
+impl Walker<'_> {
+    fn walk(&mut self, dir: &Path, depth: usize) {
+        if self.config.follow_symlinks {
+            match dir.canonicalize() {
+                Ok(real) if self.visited.contains(&real) => return,
+                Ok(real) => self.visited.push(real),
+                Err(_) => return,
+            }
+        }
+        let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+            Ok(read) => read.filter_map(Result::ok).map(|e| e.path()).collect(),
+            Err(e) => return debug!("cannot read {dir:?}: {e}"),
+        };
+        paths.sort();
+        let (mut dirs, mut found) = (Vec::new(), false);
+        for path in paths {
+            if self.ignored(&path) {
+                trace!("Ignore {path:?}");
+                continue;
+            }
+            let symlink = path
+                .symlink_metadata()
+                .is_ok_and(|m| m.file_type().is_symlink());
+            if path.is_dir() {
+                if !symlink || self.config.follow_symlinks {
+                    dirs.push(path);
+                }
+            } else if path.is_file() && self.is_manifest(&path) {
+                trace!("Found manifest {path:?}");
+                self.found.push(path);
+                found = true;
+            }
+        }
+        if (found && depth > 0) || depth >= self.config.depth {
+            return;
+        }
+        for dir in dirs {
+            self.walk(&dir, depth + 1);
+        }
+    }
+
+    /// 以`.`开头或匹配忽略规则的文件和文件夹
+    fn ignored(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with('.') {
+            return true;
+        }
+        let relative = path.strip_prefix(self.root).unwrap_or(path);
+        self.ignore.is_match(name.as_ref()) || self.ignore.is_match(relative)
+    }
+
+    fn is_manifest(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if self.config.manifests.iter().any(|m| *m == name) {
+            return true;
+        }
+        let json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if !json || path.metadata().map_or(true, |m| m.len() > MAX_MARKER_FILE) {
+            return false;
+        }
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|value| Some(value.get("$schema")?.as_str()?.contains(MANIFEST_SCHEMA)))
+            .unwrap_or(false)
+    }
+}
+
+/// 检查描述文件能否加载，返回插件信息；不能加载时返回结果和原因
+async fn inspect(
+    state: &AppState,
+    path: &Path,
+    entry: &mut ScanEntry,
+    scanned: &[ScanEntry],
+) -> Result<PluginInfo, (ScanOutcome, String)> {
+    let info = read_manifest(path)
+        .await
+        .map_err(|e| (ScanOutcome::InvalidJson, e.to_string()))?;
+    let id = PluginId::from(&info);
+    entry.id = Some(id.to_string());
+    entry.name = Some(info.name.clone());
+    if let Some(first) = scanned.iter().find(|e| e.id == entry.id) {
+        let error = format!("plugin {} is already found in {}", info.name, first.path);
+        return Err((ScanOutcome::DuplicateId, error));
+    }
+    if !Path::new(&info.libfile).is_file() {
+        let error = format!("library not found: {}", info.libfile);
+        return Err((ScanOutcome::MissingLibfile, error));
+    }
+    if state.pm.get(&id).is_some() {
+        return Err((ScanOutcome::Skipped, "already loaded".to_string()));
+    }
+    if state
+        .registry
+        .get(&id.to_string())
+        .is_some_and(|e| !e.enabled)
+    {
+        return Err((ScanOutcome::Skipped, "disabled".to_string()));
+    }
+    Ok(info)
+}
+
+fn outcome(e: &(dyn std::error::Error + 'static)) -> ScanOutcome {
+    match e.downcast_ref::<PluginError>() {
+        Some(PluginError::AbiMismatch(_)) => ScanOutcome::AbiMismatch,
+        _ => ScanOutcome::Failed,
+    }
+}
+
+fn millis(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+impl AppState {
+    /// 扫描文件夹，按依赖顺序加载其中的插件，记录每个描述文件的结果
+    pub(crate) async fn scan_dir(&self, dir: &Path) -> ScanReport {
+        let start = Instant::now();
+        let mut entries: Vec<ScanEntry> = Vec::new();
+        // 可以加载的插件：(在 entries 中的下标, 描述文件, 插件信息)
+        let mut candidates: Vec<(usize, PathBuf, PluginInfo)> = Vec::new();
+        let discovery = self.config().plugins.discovery;
+        for path in find_manifests(dir, &discovery) {
+            let begin = Instant::now();
+            let mut entry = ScanEntry {
+                path: path.to_string_lossy().to_string(),
+                id: None,
+                name: None,
+                outcome: ScanOutcome::Loaded,
+                error: None,
+                millis: 0,
+            };
+            match inspect(self, &path, &mut entry, &entries).await {
+                Ok(info) => candidates.push((entries.len(), path, info)),
+                Err((outcome, error)) => {
+                    debug!("Skip {path:?}: {error}");
+                    entry.outcome = outcome;
+                    entry.error = Some(error);
+                }
+            }
+            entry.millis = millis(begin);
+            entries.push(entry);
+        }
+
+        let infos: Vec<PluginInfo> = candidates.iter().map(|c| c.2.clone()).collect();
+        let (order, cyclic) = load_order(&infos);
+        for i in cyclic {
+            let entry = &mut entries[candidates[i].0];
+            entry.outcome = ScanOutcome::Failed;
+            entry.error = Some("dependency cycle".to_string());
+        }
+        for i in order {
+            let (index, path, info) = &candidates[i];
+            let begin = Instant::now();
+            let result = self.load(info.clone(), Some(path)).await;
+            let entry = &mut entries[*index];
+            match result {
+                Ok(id) => debug!("Loaded plugin from {path:?}: {id}"),
+                Err(e) => {
+                    entry.outcome = outcome(e.as_ref());
+                    entry.error = Some(e.to_string());
+                }
+            }
+            entry.millis += millis(begin);
+        }
+
+        let failed = entries
+            .iter()
+            .filter(|e| !matches!(e.outcome, ScanOutcome::Loaded | ScanOutcome::Skipped));
+        for entry in failed {
+            warn!(
+                "Failed to load plugin from {}: {}",
+                entry.path,
+                entry.error.as_deref().unwrap_or_default()
+            );
+        }
+        ScanReport {
+            dir: dir.to_string_lossy().to_string(),
+            entries,
+            millis: millis(start),
+        }
+    }
+
+    /// 查找项目、用户和系统插件目录中的插件，不加载
+    pub(crate) async fn discover_plugins(&self) -> Vec<DiscoveredPlugin> {
+        let config = self.config();
+        let mut result = Vec::new();
+        for (root, dir) in config.plugin_roots() {
+            for path in find_manifests(&dir, &config.plugins.discovery) {
+                let mut plugin = DiscoveredPlugin {
+                    path: path.to_string_lossy().to_string(),
+                    root,
+                    status: DiscoveryStatus::Available,
+                    id: None,
+                    name: None,
+                    version: None,
+                    error: None,
+                };
+                match read_manifest(&path).await {
+                    Ok(info) => {
+                        let id = PluginId::from(&info);
+                        plugin.status = if self.pm.get(&id).is_some() {
+                            DiscoveryStatus::Loaded
+                        } else if self
+                            .registry
+                            .get(&id.to_string())
+                            .is_some_and(|e| !e.enabled)
+                        {
+                            DiscoveryStatus::Disabled
+                        } else {
+                            DiscoveryStatus::Available
+                        };
+                        plugin.id = Some(id.to_string());
+                        plugin.name = Some(info.name);
+                        plugin.version = Some(info.version);
+                    }
+                    Err(e) => {
+                        plugin.status = DiscoveryStatus::Invalid;
+                        plugin.error = Some(e.to_string());
+                    }
+                }
+                result.push(plugin);
+            }
+        }
+        result
+    }
+
+    /// 登记并加载插件目录中发现的插件（见 [AppState::discover_plugins]），之后随注册表恢复；
+    /// 描述文件须位于项目、用户或系统插件目录中，已禁用的插件需通过 `enable_plugin` 启用
+    pub(crate) async fn register_discovered(&self, manifest: &Path) -> PluginResult<String> {
+        let config = self.config();
+        let manifest = manifest.canonicalize()?;
+        let found = config.plugin_roots().iter().any(|(_, dir)| {
+            find_manifests(dir, &config.plugins.discovery)
+                .iter()
+                .any(|path| path.canonicalize().is_ok_and(|path| path == manifest))
+        });
+        if !found {
+            return Err(format!("not a discovered plugin manifest: {}", manifest.display()).into());
+        }
+        let info = read_manifest(&manifest).await?;
+        let id = PluginId::from(&info).to_string();
+        if self.pm.get(&PluginId(id.clone().into())).is_some() {
+            return Ok(id);
+        }
+        self.load(info, Some(&manifest)).await
+    }
+}
@@ -0,0 +1,257 @@
+use crate::AppState;
+use dashmap::DashMap;
+use host_events::{HOST_TOPIC_PREFIXES, HostEvents, topic_matches};
+use libcommon::{debug, trace, warn};
+use pluginmanager::{
+    Caller, PluginId,
+    plugin::{Event, PluginResult, Value, async_trait},
+};
+use std::{
+    sync::{Arc, OnceLock, RwLock, Weak},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use window::{Emitter, WindowId, WindowLifecycle};
+
+/// 向前端发送订阅事件的事件名，内容为 [Event]
+pub const BUS_EVENT: &str = "event";
+/// 宿主发布的事件的来源
+pub const SOURCE_HOST: &str = "host";
+/// 前端发布的事件的来源
+pub const SOURCE_UI: &str = "ui";
+
+/// 插件处理单个事件的时间上限，超时后继续处理该插件的后续事件
+const DELIVER_TIMEOUT: Duration = Duration::from_secs(5);
+/// 等待分发的事件数上限，超出时丢弃新发布的事件
+const BUS_QUEUE: usize = 1024;
+/// 每个插件等待处理的事件数上限，超出时该插件丢失新的事件
+const PLUGIN_QUEUE: usize = 256;
+
+/// 事件的订阅方
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Subscriber {
+    Plugin(PluginId),
+    /// 窗口的前端，通过 [BUS_EVENT] 事件接收
+    Ui(WindowId),
+}
+
+/// 宿主、插件和前端之间的事件总线
+///
+/// 事件按发布顺序分发，插件在各自的上下文中通过 `on_event` 接收。
+/// 每个插件有各自的队列和处理任务，单个插件处理缓慢不影响其他订阅方；
+/// 发布方无需等待订阅方处理完成
+#[derive(Default)]
+pub struct EventBus {
+    subscriptions: RwLock<Vec<(Subscriber, String)>>,
+    sender: OnceLock<mpsc::Sender<Event>>,
+    emitter: OnceLock<Emitter>,
+    /// 插件的事件队列，插件第一次收到事件时创建
+    queues: DashMap<PluginId, mpsc::Sender<Event>>,
+}
+
+impl EventBus {
+    /// 设置向前端发送事件的通道；未设置（如无窗口模式）时前端订阅不会收到事件
+    pub fn set_emitter(&self, emitter: Emitter) {
+        let _ = self.emitter.set(emitter);
+    }
+
+    fn subscribe(&self, subscriber: Subscriber, pattern: String) {
+        let mut subscriptions = self.write();
+        let subscription = (subscriber, pattern);
+        if !subscriptions.contains(&subscription) {
+            subscriptions.push(subscription);
+        }
+    }
+
+    fn unsubscribe(&self, subscriber: &Subscriber, pattern: &str) {
+        self.write()
+            .retain(|(s, p)| !(s == subscriber && p == pattern));
+    }
+
+    /// 取消插件的所有订阅，其队列中未处理的事件被丢弃
+    pub fn remove(&self, plugin: &PluginId) {
+        let subscriber = Subscriber::Plugin(plugin.clone());
+        self.write().retain(|(s, _)| s != &subscriber);
+        self.queues.remove(plugin);
+    }
+
+    /// 取消窗口的所有订阅
+    fn remove_window(&self, window: &WindowId) {
+        let subscriber = Subscriber::Ui(window.clone());
+        self.write().retain(|(s, _)| s != &subscriber);
+    }
+
+    /// 发布事件；分发尚未开始（见 [AppState::start_events]）或队列已满时丢弃
+    pub fn publish(&self, topic: impl Into<String>, source: impl Into<String>, payload: Value) {
+        let event = Event {
+            topic: topic.into(),
+            source: source.into(),
+            payload,
+        };
+        trace!("publish {} from {}", event.topic, event.source);
+        match self.sender.get() {
+            Some(sender) => {
+                if let Err(mpsc::error::TrySendError::Full(event)) = sender.try_send(event) {
+                    warn!("event queue is full, drop {}", event.topic);
+                }
+            }
+            None => debug!("event bus is not started, drop {}", event.topic),
+        }
+    }
+
+    /// 匹配主题的订阅方，同一订阅方只出现一次
+    fn subscribers(&self, topic: &str) -> Vec<Subscriber> {
+        let mut result: Vec<Subscriber> = Vec::new();
+        let subscriptions = self.subscriptions.read().unwrap_or_else(|e| e.into_inner());
+        for (subscriber, pattern) in subscriptions.iter() {
+            if topic_matches(pattern, topic) && !result.contains(subscriber) {
+                result.push(subscriber.clone());
+            }
+        }
+        result
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Vec<(Subscriber, String)>> {
+        self.subscriptions
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 宿主主题只能由宿主发布
+fn check_topic(topic: &str) -> Result<(), String> {
+    if topic.is_empty() || topic.contains('*') {
+        return Err(format!("invalid topic: {topic:?}"));
+    }
+    if HOST_TOPIC_PREFIXES.iter().any(|p| topic.starts_with(p)) {
+        return Err(format!("topic {topic} is reserved for the host"));
+    }
+    Ok(())
+}
+
+impl AppState {
+    /// 开始分发事件，只有第一次调用生效
+    pub fn start_events(self: &Arc<Self>) {
+        let (tx, mut rx) = mpsc::channel::<Event>(BUS_QUEUE);
+        if self.events.sender.set(tx).is_err() {
+            return;
+        }
+        let state = Arc::downgrade(self);
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let Some(state) = state.upgrade() else {
+                    break;
+                };
+                state.dispatch(event).await;
+            }
+        });
+    }
+
+    /// 将事件放入各订阅方的队列，不等待插件处理
+    async fn dispatch(self: &Arc<Self>, event: Event) {
+        for subscriber in self.events.subscribers(&event.topic) {
+            match subscriber {
+                Subscriber::Plugin(id) => {
+                    let queue = self
+                        .events
+                        .queues
+                        .entry(id.clone())
+                        .or_insert_with(|| self.deliver_to(id.clone()))
+                        .clone();
+                    if let Err(mpsc::error::TrySendError::Full(event)) =
+                        queue.try_send(event.clone())
+                    {
+                        warn!("event queue of plugin {id} is full, drop {}", event.topic);
+                    }
+                }
+                Subscriber::Ui(window) => {
+                    match (self.events.emitter.get(), serde_json::to_value(&event)) {
+                        (Some(emitter), Ok(value)) => emitter.emit_to(&window, BUS_EVENT, value),
+                        (_, Err(e)) => warn!("failed to serialize event {}: {e}", event.topic),
+                        (None, _) => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// 启动插件的事件处理任务，队列被移除（见 [EventBus::remove]）后结束
+    fn deliver_to(self: &Arc<Self>, id: PluginId) -> mpsc::Sender<Event> {
+        let (tx, mut rx) = mpsc::channel::<Event>(PLUGIN_QUEUE);
+        let state: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let Some(state) = state.upgrade() else {
+                    break;
+                };
+                let deliver = state.pm.notify(&id, event.clone(), state.as_ref());
+                match tokio::time::timeout(DELIVER_TIMEOUT, deliver).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => warn!("plugin {id} failed to handle {}: {e}", event.topic),
+                    Err(_) => warn!("plugin {id} timed out handling {}", event.topic),
+                }
+            }
+        });
+        tx
+    }
+
+    /// 发布宿主事件
+    pub(crate) fn publish_host(&self, topic: &str, payload: Value) {
+        self.events.publish(topic, SOURCE_HOST, payload);
+    }
+
+    /// 窗口的前端订阅主题，事件只发送给该窗口
+    pub fn subscribe_ui(&self, window: WindowId, pattern: String) {
+        self.events.subscribe(Subscriber::Ui(window), pattern);
+    }
+
+    pub fn unsubscribe_ui(&self, window: WindowId, pattern: &str) {
+        self.events.unsubscribe(&Subscriber::Ui(window), pattern);
+    }
+
+    /// 前端发布事件
+    pub fn publish_ui(&self, topic: String, payload: Value) -> Result<(), String> {
+        check_topic(&topic)?;
+        self.events.publish(topic, SOURCE_UI, payload);
+        Ok(())
+    }
+
+    /// 将窗口生命周期事件发布为 `window.*` 主题，窗口关闭时取消其订阅
+    pub fn publish_window(&self, id: &WindowId, event: WindowLifecycle) {
+        if event == WindowLifecycle::Closed {
+            self.events.remove_window(id);
+        }
+        let topic = format!("{}{event}", host_events::TOPIC_WINDOW_PREFIX);
+        self.publish_host(&topic, serde_json::json!({ "id": id.to_string() }));
+    }
+}
+
+fn caller() -> PluginResult<Caller> {
+    Caller::current().ok_or_else(|| "events are only available to plugins".into())
+}
+
+#[async_trait]
+impl HostEvents for AppState {
+    async fn subscribe(&self, arg: String) -> PluginResult<()> {
+        let caller = caller()?;
+        debug!("plugin {} subscribe {arg}", caller.name);
+        self.events
+            .subscribe(Subscriber::Plugin(caller.plugin), arg);
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, arg: String) -> PluginResult<()> {
+        let caller = caller()?;
+        self.events
+            .unsubscribe(&Subscriber::Plugin(caller.plugin), &arg);
+        Ok(())
+    }
+
+    async fn publish(&self, arg: (String, Value)) -> PluginResult<()> {
+        let caller = caller()?;
+        let (topic, payload) = arg;
+        check_topic(&topic)?;
+        self.events.publish(topic, caller.name, payload);
+        Ok(())
+    }
+}
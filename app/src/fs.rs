@@ -0,0 +1,369 @@
+use crate::AppState;
+use anyhow::{Result, anyhow};
+use dashmap::DashMap;
+use host_fs::{FsChange, FsChangeKind, FsEntry, HostFs, ReadRequest, WriteRequest};
+use libcommon::{debug, warn};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use pluginmanager::{
+    Caller, PluginId,
+    plugin::{Binary, PluginResult, async_trait},
+};
+use std::{
+    collections::VecDeque,
+    path::{Component, Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::UNIX_EPOCH,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// 单次读取的上限
+const MAX_READ: u64 = 4 * 1024 * 1024;
+/// 每个监听保留的变化条数，超出时丢弃最早的
+const MAX_CHANGES: usize = 1000;
+
+/// 插件对文件夹的监听
+#[derive(Default)]
+pub struct Watches {
+    watches: DashMap<u64, Watch>,
+    next: AtomicU64,
+}
+
+struct Watch {
+    plugin: PluginId,
+    changes: Arc<Mutex<VecDeque<FsChange>>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl Watches {
+    fn add(&self, plugin: PluginId, path: &Path) -> Result<u64> {
+        let changes = Arc::new(Mutex::new(VecDeque::new()));
+        let buffer = changes.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => return warn!("watch error: {e}"),
+                };
+                let kind = match event.kind {
+                    EventKind::Create(_) => FsChangeKind::Create,
+                    EventKind::Modify(_) => FsChangeKind::Modify,
+                    EventKind::Remove(_) => FsChangeKind::Remove,
+                    EventKind::Access(_) => return,
+                    _ => FsChangeKind::Other,
+                };
+                let paths = event
+                    .paths
+                    .iter()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect();
+                let mut buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+                if buffer.len() >= MAX_CHANGES {
+                    buffer.pop_front();
+                }
+                buffer.push_back(FsChange { kind, paths });
+            })?;
+        watcher.watch(path, RecursiveMode::Recursive)?;
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        let watch = Watch {
+            plugin,
+            changes,
+            _watcher: watcher,
+        };
+        self.watches.insert(id, watch);
+        Ok(id)
+    }
+
+    fn take(&self, plugin: &PluginId, id: u64) -> Result<Vec<FsChange>> {
+        let watch = self
+            .watches
+            .get(&id)
+            .filter(|w| &w.plugin == plugin)
+            .ok_or_else(|| anyhow!("watch not found: {id}"))?;
+        let mut changes = watch.changes.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(changes.drain(..).collect())
+    }
+
+    fn remove(&self, plugin: &PluginId, id: u64) -> bool {
+        self.watches
+            .remove_if(&id, |_, w| &w.plugin == plugin)
+            .is_some()
+    }
+
+    /// 停止插件的所有监听
+    pub fn clear(&self, plugin: &PluginId) {
+        self.watches.retain(|_, w| &w.plugin != plugin);
+    }
+}
+
+/// 用户授权插件访问的路径记录在注册表中，如 `HostFs:/home/user/docs`
+fn path_capability(path: &Path) -> String {
+    format!("{}:{}", host_fs::HOST_FS_NAME, path.display())
+}
+
+/// 解析符号链接及`..`后的真实路径；路径不存在时解析其已存在的上级，
+/// 其余部分中不能含有`..`（无法确定其上级是否为符号链接）
+fn canonicalize(path: &Path) -> Result<PathBuf> {
+    let mut tail = Vec::new();
+    let mut curr = path;
+    loop {
+        if let Ok(base) = curr.canonicalize() {
+            return Ok(tail.into_iter().rev().fold(base, |p, name| p.join(name)));
+        }
+        let name = match curr.components().next_back() {
+            Some(Component::Normal(name)) => name,
+            Some(Component::ParentDir) => {
+                return Err(anyhow!(
+                    "path must not contain `..` after a missing directory: {path:?}"
+                ));
+            }
+            _ => return Err(anyhow!("invalid path: {path:?}")),
+        };
+        tail.push(name);
+        curr = curr
+            .parent()
+            .ok_or_else(|| anyhow!("invalid path: {path:?}"))?;
+    }
+}
+
+/// 按路径组成部分比较，`/data/a-b` 不在 `/data/a` 之内
+fn within(path: &Path, roots: &[PathBuf]) -> bool {
+    roots.iter().any(|root| path.starts_with(root))
+}
+
+impl AppState {
+    /// 插件可以访问的路径：数据文件夹、缓存文件夹及用户授权的路径
+    fn fs_roots(&self, caller: &Caller) -> Result<Vec<PathBuf>> {
+        let mut roots = vec![
+            self.storage.data_dir(&caller.name)?.canonicalize()?,
+            self.storage.cache_dir(&caller.name)?.canonicalize()?,
+        ];
+        let prefix = path_capability(Path::new(""));
+        if let Some(entry) = self.registry.get(&caller.plugin.to_string()) {
+            let granted = entry
+                .grants
+                .into_iter()
+                .filter(|(_, granted)| *granted)
+                .filter_map(|(capability, _)| capability.strip_prefix(&prefix).map(PathBuf::from));
+            roots.extend(granted);
+        }
+        Ok(roots)
+    }
+
+    /// 将插件传入的路径解析为真实路径，不在可访问范围内时返回错误
+    fn fs_resolve(&self, caller: &Caller, path: &str) -> Result<PathBuf> {
+        let raw = Path::new(path);
+        let path = match raw.is_absolute() {
+            true => raw.to_path_buf(),
+            false => self.storage.data_dir(&caller.name)?.join(raw),
+        };
+        let path = canonicalize(&path)?;
+        if within(&path, &self.fs_roots(caller)?) {
+            Ok(path)
+        } else {
+            Err(anyhow!(
+                "access denied: plugin {} cannot access {raw:?}",
+                caller.name
+            ))
+        }
+    }
+
+    /// 用户授权或拒绝插件访问路径（含子路径），返回记录的真实路径
+    pub fn set_path_grant(&self, plugin: &str, path: &Path, granted: bool) -> Result<PathBuf> {
+        if self.registry.get(plugin).is_none() {
+            return Err(anyhow!("plugin is not registered: {plugin}"));
+        }
+        let path = canonicalize(&std::path::absolute(path)?)?;
+        self.registry
+            .set_grant(plugin, &path_capability(&path), granted);
+        Ok(path)
+    }
+}
+
+/// 文件系统只对插件开放，宿主自身调用时没有调用方
+fn caller() -> Result<Caller> {
+    Caller::current().ok_or_else(|| anyhow!("filesystem is only available to plugins"))
+}
+
+async fn entry(path: &Path) -> Result<FsEntry> {
+    let meta = tokio::fs::metadata(path).await?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    Ok(FsEntry {
+        path: path.to_string_lossy().to_string(),
+        dir: meta.is_dir(),
+        size: meta.len(),
+        modified,
+    })
+}
+
+#[async_trait]
+impl HostFs for AppState {
+    async fn fs_read(&self, arg: ReadRequest) -> PluginResult<Binary> {
+        let path = self.fs_resolve(&caller()?, &arg.path)?;
+        let mut file = tokio::fs::File::open(&path).await?;
+        let remain = file.metadata().await?.len().saturating_sub(arg.offset);
+        let len = match arg.len {
+            Some(len) => len.min(remain).min(MAX_READ),
+            None if remain > MAX_READ => {
+                return Err(format!(
+                    "file is larger than {MAX_READ} bytes, read it in chunks: {}",
+                    arg.path
+                )
+                .into());
+            }
+            None => remain,
+        };
+        file.seek(std::io::SeekFrom::Start(arg.offset)).await?;
+        let mut data = vec![0; len as usize];
+        file.read_exact(&mut data).await?;
+        let mime = mime_guess::from_path(&path).first_or_octet_stream();
+        Ok(Binary::new(mime.to_string(), data))
+    }
+
+    async fn fs_write(&self, arg: WriteRequest) -> PluginResult<()> {
+        let path = self.fs_resolve(&caller()?, &arg.path)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(arg.append)
+            .truncate(!arg.append)
+            .open(&path)
+            .await?;
+        file.write_all(&arg.data.data).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn fs_list(&self, arg: String) -> PluginResult<Vec<FsEntry>> {
+        let path = self.fs_resolve(&caller()?, &arg)?;
+        let mut dir = tokio::fs::read_dir(&path).await?;
+        let mut result = Vec::new();
+        while let Some(item) = dir.next_entry().await? {
+            result.push(entry(&item.path()).await?);
+        }
+        result.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(result)
+    }
+
+    async fn fs_stat(&self, arg: String) -> PluginResult<FsEntry> {
+        let path = self.fs_resolve(&caller()?, &arg)?;
+        Ok(entry(&path).await?)
+    }
+
+    async fn fs_mkdir(&self, arg: String) -> PluginResult<()> {
+        let path = self.fs_resolve(&caller()?, &arg)?;
+        Ok(tokio::fs::create_dir_all(&path).await?)
+    }
+
+    async fn fs_remove(&self, arg: String) -> PluginResult<()> {
+        let path = self.fs_resolve(&caller()?, &arg)?;
+        if tokio::fs::metadata(&path).await?.is_dir() {
+            tokio::fs::remove_dir_all(&path).await?;
+        } else {
+            tokio::fs::remove_file(&path).await?;
+        }
+        Ok(())
+    }
+
+    async fn fs_watch(&self, arg: String) -> PluginResult<u64> {
+        let caller = caller()?;
+        let path = self.fs_resolve(&caller, &arg)?;
+        let id = self.watches.add(caller.plugin, &path)?;
+        debug!("plugin {} watch({id}) {path:?}", caller.name);
+        Ok(id)
+    }
+
+    async fn fs_changes(&self, arg: u64) -> PluginResult<Vec<FsChange>> {
+        Ok(self.watches.take(&caller()?.plugin, arg)?)
+    }
+
+    async fn fs_unwatch(&self, arg: u64) -> PluginResult<()> {
+        if !self.watches.remove(&caller()?.plugin, arg) {
+            return Err(format!("watch not found: {arg}").into());
+        }
+        Ok(())
+    }
+
+    async fn fs_request_access(&self, arg: String) -> PluginResult<bool> {
+        let caller = caller()?;
+        if !Path::new(&arg).is_absolute() {
+            return Err(format!("path must be absolute: {arg}").into());
+        }
+        if self.fs_resolve(&caller, &arg).is_ok() {
+            return Ok(true);
+        }
+        let path = canonicalize(Path::new(&arg))?;
+        let info = self
+            .pm
+            .get(&caller.plugin)
+            .ok_or_else(|| format!("plugin {} is not loaded", caller.plugin))?;
+        let capability = path_capability(&path);
+        Ok(self.confirm(&caller.plugin, &info, &capability).await)
+    }
+
+    async fn cache_dir(&self, _: ()) -> PluginResult<String> {
+        let dir = self.storage.cache_dir(&caller()?.name)?;
+        Ok(dir.to_string_lossy().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn canonicalize_missing_tail() {
+        let dir = TempDir::new();
+        let path = canonicalize(&dir.join("a/b.txt")).unwrap();
+        assert_eq!(path, dir.join("a").join("b.txt"));
+    }
+
+    #[test]
+    fn canonicalize_resolves_existing_parent_dir() {
+        let dir = TempDir::new();
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        let path = canonicalize(&dir.join("sub/../file")).unwrap();
+        assert_eq!(path, dir.join("file"));
+    }
+
+    #[test]
+    fn canonicalize_rejects_parent_dir_after_missing() {
+        let dir = TempDir::new();
+        for path in ["missing/../../etc/passwd", "missing/.."] {
+            let err = canonicalize(&dir.join(path)).unwrap_err();
+            assert!(err.to_string().contains("`..`"), "{path}: {err}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_out_of_root_is_denied() {
+        let (root, outside) = (TempDir::new(), TempDir::new());
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        let path = canonicalize(&root.join("link/secret")).unwrap();
+        assert_eq!(path, outside.join("secret"));
+        assert!(!within(&path, &[root.to_path_buf()]));
+    }
+
+    #[test]
+    fn grants_match_whole_components() {
+        let roots = [PathBuf::from("/data/a"), PathBuf::from("/home/user/docs")];
+        assert!(within(Path::new("/data/a"), &roots));
+        assert!(within(Path::new("/data/a/b/c"), &roots));
+        assert!(within(Path::new("/home/user/docs/x.txt"), &roots));
+        assert!(!within(Path::new("/data/a-b"), &roots));
+        assert!(!within(Path::new("/data"), &roots));
+        assert!(!within(Path::new("/home/user"), &roots));
+    }
+}
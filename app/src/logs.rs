@@ -0,0 +1,189 @@
+use dashmap::DashMap;
+use libcommon::warn;
+use pluginmanager::{
+    Caller,
+    plugin::{LogLevel, LogRecord, Value},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{Receiver, SyncSender, sync_channel},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// 等待写入文件的记录数上限，写入跟不上时新的记录不写文件（仍保留在内存中并转发到宿主日志）
+const FILE_QUEUE: usize = 4096;
+
+/// 插件日志：按插件保存最近的记录，并可按插件写入日志文件
+pub struct PluginLogs {
+    capacity: usize,
+    /// 写日志文件的线程，未设置插件日志文件夹时为空
+    files: Option<SyncSender<LogEntry>>,
+    buffers: DashMap<String, Mutex<VecDeque<LogEntry>>>,
+    next: AtomicU64,
+}
+
+/// 宿主记录的一条插件日志
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// 进程内递增的序号，用于分页
+    pub seq: u64,
+    /// unix 时间戳（毫秒）
+    pub time: u64,
+    pub plugin: String,
+    pub name: String,
+    pub call_id: u64,
+    pub level: LogLevel,
+    pub message: String,
+    pub fields: serde_json::Map<String, Value>,
+    pub spans: Vec<String>,
+}
+
+/// 一页日志
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogPage {
+    pub entries: Vec<LogEntry>,
+    /// 下一页的起始序号，传给下一次查询的 `after`
+    pub next: u64,
+}
+
+impl PluginLogs {
+    /// `dir` 为插件日志文件夹，每个插件写入 `<插件 id>.log`，为空时不写文件
+    pub fn new(capacity: usize, dir: Option<PathBuf>) -> Self {
+        let files = dir.map(|dir| {
+            let (tx, rx) = sync_channel(FILE_QUEUE);
+            std::thread::spawn(move || write_files(&dir, rx));
+            tx
+        });
+        Self {
+            capacity,
+            files,
+            buffers: DashMap::new(),
+            next: AtomicU64::new(1),
+        }
+    }
+
+    /// 记录插件日志并转发到宿主日志
+    pub fn record(&self, caller: &Caller, record: LogRecord) {
+        let entry = LogEntry {
+            seq: self.next.fetch_add(1, Ordering::Relaxed),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            plugin: caller.plugin.to_string(),
+            name: caller.name.clone(),
+            call_id: caller.call_id,
+            level: record.level,
+            message: record.message,
+            fields: record.fields,
+            spans: record.spans,
+        };
+        forward(&entry);
+        if let Some(files) = &self.files {
+            let _ = files.try_send(entry.clone());
+        }
+
+        let buffer = self.buffers.entry(entry.plugin.clone()).or_default();
+        let mut buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+        if buffer.len() >= self.capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(entry);
+    }
+
+    /// 按序号分页读取插件日志，只返回不低于 `level` 的记录
+    pub fn page(&self, plugin: &str, after: u64, limit: usize, level: LogLevel) -> LogPage {
+        let mut page = LogPage {
+            entries: Vec::new(),
+            next: after,
+        };
+        let Some(buffer) = self.buffers.get(plugin) else {
+            return page;
+        };
+        let buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+        // 被级别过滤掉的记录也推进 next，避免轮询时重复扫描
+        for entry in buffer.iter().filter(|e| e.seq > after) {
+            if page.entries.len() >= limit {
+                break;
+            }
+            page.next = entry.seq;
+            if entry.level >= level {
+                page.entries.push(entry.clone());
+            }
+        }
+        page
+    }
+
+    /// 清空插件的缓存日志
+    pub fn clear(&self, plugin: &str) {
+        self.buffers.remove(plugin);
+    }
+}
+
+/// 写日志文件的线程：按插件保持文件打开，连续到达的记录写完后统一刷新；
+/// 文件以插件 id 命名，插件名由插件自行声明，不能用作路径
+fn write_files(dir: &Path, rx: Receiver<LogEntry>) {
+    let mut files: HashMap<String, BufWriter<File>> = HashMap::new();
+    while let Ok(entry) = rx.recv() {
+        for entry in std::iter::once(entry).chain(rx.try_iter()) {
+            let path = dir.join(format!("{}.log", entry.plugin));
+            let result = match files.get_mut(&entry.plugin) {
+                Some(file) => Ok(file),
+                None => open_log(dir, &path)
+                    .map(|file| files.entry(entry.plugin.clone()).or_insert(file)),
+            }
+            .and_then(|file| writeln!(file, "{}", serde_json::to_string(&entry)?));
+            if let Err(e) = result {
+                warn!("Failed to write plugin log {path:?}: {e}");
+            }
+        }
+        for file in files.values_mut() {
+            if let Err(e) = file.flush() {
+                warn!("Failed to flush plugin log: {e}");
+            }
+        }
+    }
+}
+
+fn open_log(dir: &Path, path: &Path) -> std::io::Result<BufWriter<File>> {
+    std::fs::create_dir_all(dir)?;
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    Ok(BufWriter::new(file))
+}
+
+/// 以 `plugin::<插件名>` 为 target 输出到宿主日志
+fn forward(entry: &LogEntry) {
+    let level = match entry.level {
+        LogLevel::Trace => log::Level::Trace,
+        LogLevel::Debug => log::Level::Debug,
+        LogLevel::Info => log::Level::Info,
+        LogLevel::Warn => log::Level::Warn,
+        LogLevel::Error => log::Level::Error,
+    };
+    let target = format!("plugin::{}", entry.name);
+    let spans = entry.spans.join(">");
+    let fields = entry
+        .fields
+        .iter()
+        .map(|(k, v)| format!(" {k}={v}"))
+        .collect::<String>();
+    log::log!(
+        target: &target,
+        level,
+        "[{}#{}] {spans} {}{fields}",
+        entry.plugin,
+        entry.call_id,
+        entry.message
+    );
+}
@@ -1,44 +1,244 @@
+mod cli;
 mod cmd;
+mod config;
 mod context;
+mod discovery;
+mod events;
+mod fs;
+mod logs;
+mod metrics;
+mod package;
+mod permission;
+mod registry;
 mod server;
+mod storage;
+#[cfg(test)]
+mod testutil;
 use crate::{
-    cmd::{callplugin, listplugins, scan},
+    cli::{Cli, Command, RunArgs},
+    cmd::{
+        answerpermission, callplugin, disableplugin, discoverplugins, enableplugin, getconfig,
+        listplugins, pluginlogs, pluginmetrics, publishevent, registerplugin, restorereport, scan,
+        setconfig, subscribeevent, unsubscribeevent, wipeplugindata,
+    },
+    config::AppConfig,
+    events::EventBus,
+    fs::Watches,
+    logs::PluginLogs,
+    permission::{PROMPT_PAGE, Prompts},
+    registry::{Registry, RestoreReport},
     server::Server,
+    storage::Storage,
+};
+use libcommon::prelude::*;
+use pluginmanager::{CallMetrics, PluginManager, Verifier, parse_version};
+use std::sync::{Arc, Mutex, RwLock};
+use window::{
+    Access, BridgeHandlers, LogicalSize, WebViewBuilder, WindowBuilder, WindowManager, generate,
 };
-use libcommon::{New, prelude::*};
-use pluginmanager::PluginManager;
-use window::{WindowCreateExt, WindowManager, generate};
 
 #[tokio::main]
 #[logsetup(level = trace)]
 async fn main() -> Result<()> {
-    let server = Server::new(3030);
+    let command = Cli::command();
+    let config = AppConfig::load(command.common().config.as_deref(), command.overrides())?;
+    log::set_max_level(config.log_level());
+
+    match command {
+        Command::Run(args) => run(args, config).await,
+        Command::Plugins { command, .. } => {
+            let state = app_state(config)?;
+            cli::plugins(&state, command).await
+        }
+        Command::Call {
+            plugin,
+            method,
+            params,
+            ..
+        } => {
+            let state = app_state(config)?;
+            cli::call(&state, &plugin, &method, &params).await
+        }
+    }
+}
+
+fn app_state(config: AppConfig) -> Result<Arc<AppState>> {
+    let verifier = Verifier::new(config.trust.policy, &config.trust.keys)?;
+    let pm = PluginManager::default()
+        .with_verifier(verifier)
+        .with_limits(config.plugins.limits.clone())
+        .with_host_api(parse_version(::context::HOST_API_VERSION)?);
+    let server = Server::new(config.server.port)
+        .with_bind(&config.server.bind)
+        .with_metrics(config.server.metrics);
+    let registry = Registry::open(config.registry_path());
+    let logs = PluginLogs::new(config.log.buffer, config.plugin_log_dir());
+    let storage = Storage::new(
+        config.storage_dir(),
+        config.cache_dir(),
+        config.storage.quota,
+    );
+    let state = Arc::new(AppState {
+        pm,
+        server,
+        config: RwLock::new(config),
+        registry,
+        prompts: Prompts::default(),
+        logs,
+        storage,
+        watches: Watches::default(),
+        events: EventBus::default(),
+        bridge_metrics: CallMetrics::default(),
+        restored: Mutex::default(),
+    });
+    state.start_events();
+    Ok(state)
+}
+
+/// 启动应用
+async fn run(args: RunArgs, config: AppConfig) -> Result<()> {
+    let headless = args.headless;
+    let window = config.window.clone();
+    let token_path = config.token_path();
+    let state = app_state(config)?;
+    let server = state.server.clone().with_frontend(!headless);
+    if let Some(path) = token_path
+        && let Err(e) = server.save_token(&path)
+    {
+        warn!("Failed to save bridge token to {path:?}: {e}");
+    }
     let url = server.window_url();
-    let server2 = server.clone();
 
+    let handlers = BridgeHandlers::default();
+    handlers.register(generate!(
+        listplugins,
+        scan,
+        discoverplugins,
+        registerplugin,
+        callplugin,
+        enableplugin,
+        disableplugin,
+        getconfig,
+        setconfig,
+        answerpermission,
+        pluginlogs,
+        wipeplugindata,
+        subscribeevent,
+        unsubscribeevent,
+        publishevent,
+        pluginmetrics,
+        restorereport
+    ));
+    // 只有以下只读或调用插件的命令可经由 HTTP/WebSocket 接口调用，其余命令只允许窗口调用
+    handlers.restrict(
+        ["listplugins", "callplugin", "getconfig", "restorereport"],
+        Access::Any,
+    );
+    // 主窗口页面读取二进制响应（window_url 只有根路径）
+    handlers.blobs().allow_origin(url.trim_end_matches('/'));
+    handlers.set_observer(state.clone());
+
+    // 恢复结果作为 `plugin.restored` 事件发布，也可通过 `restorereport` 命令查询
+    state.restore_plugins().await;
+    if headless {
+        return run_headless(state, handlers).await;
+    }
+
+    let listener = Arc::downgrade(&state);
+    let wm = WindowManager::with_state(state.clone())
+        .with_handlers(handlers.clone())
+        .with_listener(move |id, event| {
+            if let Some(state) = listener.upgrade() {
+                state.publish_window(id, event);
+            }
+        });
+    state.events.set_emitter(wm.emitter());
+    let (server_state, server_handlers) = (state.clone(), handlers.clone());
     tokio::spawn(async move {
-        match server2.run().await {
+        match server.run(server_state, server_handlers).await {
             Ok(_) => debug!("server stopped"),
             Err(e) => error!("server start failed: {e}"),
         }
     });
 
-    let file_dir = std::env::current_dir()?
-        .join("dist")
-        .to_string_lossy()
-        .to_string();
-    let pm = PluginManager::default();
-    let state = AppState::new(pm, server, file_dir);
-    let wm = WindowManager::with_state(state.into());
-    wm.create_window("main", url)?;
-    wm.register_handler(generate!(listplugins, scan, callplugin));
+    let win = WindowBuilder::new()
+        .with_title(window.title)
+        .with_inner_size(LogicalSize::new(window.width, window.height))
+        .with_decorations(window.decorations);
+    wm.create(win, WebViewBuilder::new().with_url(url))?;
+    // 插件页面挂载在宿主页面中且同源，权限确认放在独立窗口，只接受该窗口的回复
+    let prompt = WindowBuilder::new()
+        .with_title("权限确认")
+        .with_inner_size(LogicalSize::new(360, 200))
+        .with_resizable(false)
+        .with_always_on_top(true)
+        .with_visible(false);
+    let prompt = wm.create(prompt, WebViewBuilder::new().with_html(PROMPT_PAGE))?;
+    state.prompts.set_window(wm.emitter(), prompt.clone());
+    handlers.restrict(["answerpermission"], Access::Window(prompt));
     info!("launch window");
     wm.run()
 }
 
-#[derive(New)]
+/// 无窗口模式：不创建 tao/wry 窗口，只启动服务器并加载插件目录下的插件；
+/// 开放的 bridge 命令通过 HTTP/WebSocket 访问，收到退出信号后关闭连接、卸载插件并退出。
+/// 不另外提供 Unix socket：HTTP 接口只监听本机并需携带令牌，CI 中用 curl 即可调用，
+/// 再提供一套接入方式需要重复实现鉴权和命令限制
+async fn run_headless(state: Arc<AppState>, handlers: BridgeHandlers<AppState>) -> Result<()> {
+    info!("launch headless");
+    let server = state.server.clone();
+    server
+        .run_until(state.clone(), handlers, shutdown_signal())
+        .await?;
+
+    for (id, _) in state.pm.list() {
+        state.pm.unload(&id);
+    }
+    info!("headless stopped");
+    Ok(())
+}
+
+/// 等待 Ctrl+C 或 SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for ctrl-c: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("received ctrl-c, shutting down"),
+        _ = terminate => info!("received SIGTERM, shutting down"),
+    }
+}
+
 pub struct AppState {
     pub pm: PluginManager,
     pub server: Server,
-    pub plugin_dir: String,
+    pub config: RwLock<AppConfig>,
+    pub registry: Registry,
+    pub prompts: Prompts,
+    pub logs: PluginLogs,
+    pub storage: Storage,
+    pub watches: Watches,
+    pub events: EventBus,
+    pub bridge_metrics: CallMetrics,
+    /// 启动时恢复插件的结果
+    pub restored: Mutex<RestoreReport>,
 }
@@ -0,0 +1,115 @@
+use crate::AppState;
+use pluginmanager::{CallStats, LATENCY_BUCKETS, PluginId};
+use serde_json::json;
+use std::{fmt::Write, time::Duration};
+use window::BridgeObserver;
+
+/// Prometheus 指标名前缀
+const METRIC_PREFIX: &str = "start";
+
+impl BridgeObserver for AppState {
+    fn start(&self, command: &str) {
+        self.bridge_metrics.begin("bridge", command);
+    }
+
+    fn finish(&self, command: &str, elapsed: Duration, ok: bool) {
+        self.bridge_metrics.end("bridge", command, elapsed, ok);
+    }
+}
+
+impl AppState {
+    /// 插件调用和 bridge 命令的统计；插件的统计以插件 id 为 `target`，并附带插件名 `name`
+    pub fn metrics(&self) -> serde_json::Value {
+        let plugins: Vec<serde_json::Value> = self
+            .pm
+            .metrics()
+            .snapshot()
+            .into_iter()
+            .map(|s| {
+                let name = self.plugin_name(&s.target);
+                let mut value = json!(s);
+                value["name"] = name.into();
+                value
+            })
+            .collect();
+        json!({
+            "buckets": LATENCY_BUCKETS,
+            "plugins": plugins,
+            "bridge": self.bridge_metrics.snapshot(),
+        })
+    }
+
+    /// 已加载插件的名称，插件已卸载时为空
+    fn plugin_name(&self, id: &str) -> String {
+        self.pm
+            .get(&PluginId(id.into()))
+            .map(|info| info.name)
+            .unwrap_or_default()
+    }
+
+    /// 以 Prometheus 文本格式输出统计
+    pub fn prometheus(&self) -> String {
+        let mut out = String::new();
+        let plugins = self.pm.metrics().snapshot();
+        write_family(&mut out, "plugin_call", &plugins, |s| {
+            format!(
+                "plugin=\"{}\",name=\"{}\",method=\"{}\"",
+                escape(&s.target),
+                escape(&self.plugin_name(&s.target)),
+                escape(&s.method)
+            )
+        });
+        let bridge = self.bridge_metrics.snapshot();
+        write_family(&mut out, "bridge_call", &bridge, |s| {
+            format!("command=\"{}\"", escape(&s.method))
+        });
+        out
+    }
+}
+
+/// 输出一组指标：调用数、失败数、进行中的调用数及耗时直方图
+fn write_family(
+    out: &mut String,
+    name: &str,
+    stats: &[CallStats],
+    labels: impl Fn(&CallStats) -> String,
+) {
+    let name = format!("{METRIC_PREFIX}_{name}");
+    let _ = writeln!(out, "# TYPE {name}s_total counter");
+    for s in stats {
+        let _ = writeln!(out, "{name}s_total{{{}}} {}", labels(s), s.calls);
+    }
+    let _ = writeln!(out, "# TYPE {name}_errors_total counter");
+    for s in stats {
+        let _ = writeln!(out, "{name}_errors_total{{{}}} {}", labels(s), s.errors);
+    }
+    let _ = writeln!(out, "# TYPE {name}s_in_flight gauge");
+    for s in stats {
+        let _ = writeln!(out, "{name}s_in_flight{{{}}} {}", labels(s), s.in_flight);
+    }
+    let _ = writeln!(out, "# TYPE {name}_duration_seconds histogram");
+    for s in stats {
+        let labels = labels(s);
+        for (le, count) in LATENCY_BUCKETS.iter().zip(&s.buckets) {
+            let _ = writeln!(
+                out,
+                "{name}_duration_seconds_bucket{{{labels},le=\"{le}\"}} {count}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+            s.calls
+        );
+        let _ = writeln!(out, "{name}_duration_seconds_sum{{{labels}}} {}", s.seconds);
+        let _ = writeln!(out, "{name}_duration_seconds_count{{{labels}}} {}", s.calls);
+    }
+}
+
+/// 转义标签值中的反斜杠、引号和换行
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
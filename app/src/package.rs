@@ -0,0 +1,482 @@
+use crate::{AppState, context::read_manifest, discovery::MANIFEST_FILE};
+use anyhow::{Result, anyhow};
+use common::random_hex;
+use host_events::{TOPIC_PLUGIN_INSTALLED, TOPIC_PLUGIN_UNINSTALLED};
+use host_pluginmanager::HostPM;
+use libcommon::{debug, info, warn};
+use pluginmanager::PluginId;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Component, Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// 插件包中的描述文件
+pub const PACKAGE_MANIFEST: &str = "manifest.json";
+/// 插件包中的校验文件，格式与 `sha256sum` 输出相同：`<sha256>  <相对路径>`
+pub const PACKAGE_CHECKSUMS: &str = "checksums.txt";
+/// 安装目录下的暂存文件夹，以`.`开头不会被扫描；每次安装使用其中随机命名的子文件夹
+const STAGING_DIR: &str = ".staging";
+const BACKUP_DIR: &str = ".backup";
+
+/// 插件包描述文件
+///
+/// 插件包为 zip 或 tar(.gz) 文件，根目录下包含 [PACKAGE_MANIFEST]、[PACKAGE_CHECKSUMS]、
+/// 各平台的库文件及 UI 资源，如：
+///
+/// ```text
+/// manifest.json
+/// checksums.txt
+/// lib/linux-x86_64/libdemo.so
+/// lib/windows-x86_64/demo.dll
+/// ui/index.html
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub uiurl: String,
+    #[serde(default)]
+    pub spa: bool,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// 允许其他插件调用的方法
+    #[serde(default)]
+    pub exports: Vec<String>,
+    /// 不能并发执行的方法
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub serial: Vec<String>,
+    /// 依赖的其他插件：插件名到版本要求
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, String>,
+    /// 要求的宿主 API 版本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// 平台（见 [current_target]）到库文件相对路径的映射
+    pub libs: BTreeMap<String, String>,
+    /// 发布者公钥（base64）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    /// 平台到该平台库文件签名的映射，见 [pluginmanager::Verifier]
+    #[serde(default)]
+    pub signatures: BTreeMap<String, String>,
+    /// 安装时按当前平台从 `libs` 中选出，安装后的描述文件可直接作为 [pluginmanager::PluginInfo] 读取
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub libfile: Option<String>,
+    /// 安装时按当前平台从 `signatures` 中选出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// 当前平台，如 `linux-x86_64`、`windows-x86_64`、`macos-aarch64`
+pub fn current_target() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// 解包到 `dest` 并校验，返回选定了当前平台库文件的描述；
+/// 描述文件改存为 [MANIFEST_FILE]，安装后的插件与其他插件一样可被发现
+fn unpack(package: &Path, dest: &Path) -> Result<PackageManifest> {
+    let name = package
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let file = File::open(package)?;
+    if name.ends_with(".zip") {
+        unpack_zip(zip::ZipArchive::new(file)?, dest)?;
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        unpack_tar(tar::Archive::new(flate2::read::GzDecoder::new(file)), dest)?;
+    } else if name.ends_with(".tar") {
+        unpack_tar(tar::Archive::new(file), dest)?;
+    } else {
+        return Err(anyhow!("unsupported package format: {package:?}"));
+    }
+    verify_checksums(dest)?;
+
+    let path = dest.join(PACKAGE_MANIFEST);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("missing {PACKAGE_MANIFEST} in package: {e}"))?;
+    let mut manifest: PackageManifest = serde_json::from_str(&content)?;
+    if manifest.name.is_empty()
+        || manifest.name.starts_with('.')
+        || manifest.name.contains(['/', '\\'])
+    {
+        return Err(anyhow!("invalid plugin name: {:?}", manifest.name));
+    }
+    let target = current_target();
+    let libfile = manifest
+        .libs
+        .get(&target)
+        .ok_or_else(|| anyhow!("package does not contain a library for {target}"))?;
+    let lib = inside(dest, libfile).map_err(|e| anyhow!("invalid libfile {libfile:?}: {e}"))?;
+    if !std::fs::symlink_metadata(lib).is_ok_and(|m| m.is_file()) {
+        return Err(anyhow!("library not found in package: {libfile}"));
+    }
+    if !manifest.uiurl.is_empty()
+        && !manifest.uiurl.starts_with("http://")
+        && !manifest.uiurl.starts_with("https://")
+    {
+        inside(dest, &manifest.uiurl)
+            .map_err(|e| anyhow!("invalid uiurl {:?}: {e}", manifest.uiurl))?;
+    }
+    manifest.libfile = Some(libfile.clone());
+    manifest.signature = manifest.signatures.get(&target).cloned();
+    std::fs::write(
+        dest.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    std::fs::remove_file(&path)?;
+    Ok(manifest)
+}
+
+/// 包内的相对路径对应的真实路径，须位于包内且存在；不能是绝对路径或含有`..`
+fn inside(dest: &Path, relative: &str) -> Result<PathBuf> {
+    let path = Path::new(relative);
+    if relative.is_empty()
+        || !path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(anyhow!("must be a relative path inside the package"));
+    }
+    let real = dest
+        .join(path)
+        .canonicalize()
+        .map_err(|e| anyhow!("not found in package: {e}"))?;
+    if !real.starts_with(dest.canonicalize()?) {
+        return Err(anyhow!("points outside the package"));
+    }
+    Ok(real)
+}
+
+/// 包内的链接可指向包外的文件而绕过校验，解包时拒绝
+fn unpack_zip(mut archive: zip::ZipArchive<File>, dest: &Path) -> Result<()> {
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if file.is_symlink() {
+            return Err(anyhow!("package contains a link: {}", file.name()));
+        }
+    }
+    archive.extract(dest)?;
+    Ok(())
+}
+
+fn unpack_tar(mut archive: tar::Archive<impl std::io::Read>, dest: &Path) -> Result<()> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let kind = entry.header().entry_type();
+        if kind.is_symlink() || kind.is_hard_link() {
+            return Err(anyhow!(
+                "package contains a link: {}",
+                entry.path()?.display()
+            ));
+        }
+        entry.unpack_in(dest)?;
+    }
+    Ok(())
+}
+
+/// 校验包内每个文件都列在校验文件中且摘要一致，包内不能有链接
+fn verify_checksums(dir: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(dir.join(PACKAGE_CHECKSUMS))
+        .map_err(|e| anyhow!("missing {PACKAGE_CHECKSUMS} in package: {e}"))?;
+    let mut expected = BTreeMap::new();
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let (hash, path) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| anyhow!("invalid checksum line: {line}"))?;
+        // `sha256sum -b` 的输出在路径前带 `*`
+        let path = path
+            .trim_start()
+            .trim_start_matches('*')
+            .trim_start_matches("./");
+        expected.insert(path.to_string(), hash.to_lowercase());
+    }
+
+    for entry in WalkDir::new(dir).into_iter().filter_map(Result::ok) {
+        let relative = entry
+            .path()
+            .strip_prefix(dir)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        if entry.path_is_symlink() {
+            return Err(anyhow!("package contains a link: {relative}"));
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if relative == PACKAGE_CHECKSUMS {
+            continue;
+        }
+        let hash = expected
+            .remove(&relative)
+            .ok_or_else(|| anyhow!("file is not listed in {PACKAGE_CHECKSUMS}: {relative}"))?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut File::open(entry.path())?, &mut hasher)?;
+        if format!("{:x}", hasher.finalize()) != hash {
+            return Err(anyhow!("checksum mismatch: {relative}"));
+        }
+    }
+    if let Some(path) = expected.keys().next() {
+        return Err(anyhow!(
+            "file listed in {PACKAGE_CHECKSUMS} is missing: {path}"
+        ));
+    }
+    Ok(())
+}
+
+/// 以 `staging` 替换 `dest`，原有的 `dest` 移到 `backup`；移动失败时 `dest` 保持原样
+fn replace_dir(staging: &Path, dest: &Path, backup: &Path) -> Result<()> {
+    if dest.exists() {
+        if let Some(parent) = backup.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(dest, backup)?;
+    }
+    if let Err(e) = std::fs::rename(staging, dest) {
+        if backup.exists()
+            && let Err(e) = std::fs::rename(backup, dest)
+        {
+            warn!("failed to restore {dest:?} from {backup:?}: {e}");
+        }
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+impl AppState {
+    /// 安装插件包到插件安装目录下以插件名命名的文件夹
+    ///
+    /// 已安装同名插件时视为升级：旧版本先移到备份目录，新版本加载失败时还原旧版本
+    pub async fn install_package(&self, package: &Path) -> Result<String> {
+        let root = self.config().plugin_dir();
+        // 同时进行的安装各自使用随机命名的暂存和备份文件夹
+        let unique = random_hex();
+        let staging = root.join(STAGING_DIR).join(&unique);
+        std::fs::create_dir_all(root.join(STAGING_DIR))?;
+        std::fs::create_dir(&staging)?;
+
+        let (pkg, dir) = (package.to_path_buf(), staging.clone());
+        let manifest = match tokio::task::spawn_blocking(move || unpack(&pkg, &dir)).await? {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&staging);
+                let _ = std::fs::remove_dir(root.join(STAGING_DIR));
+                return Err(e);
+            }
+        };
+        debug!(
+            "unpacked {package:?}: {} {}",
+            manifest.name, manifest.version
+        );
+
+        let dest = root.join(&manifest.name);
+        let backup = root.join(BACKUP_DIR).join(&unique);
+        let old = self.installed(&dest).await;
+        if let Some(id) = &old {
+            self.unload(&PluginId(id.clone().into()));
+        }
+        if let Err(e) = replace_dir(&staging, &dest, &backup) {
+            warn!("install {} failed: {e}", manifest.name);
+            let _ = std::fs::remove_dir_all(&staging);
+            let _ = std::fs::remove_dir(root.join(STAGING_DIR));
+            let _ = std::fs::remove_dir(root.join(BACKUP_DIR));
+            if old.is_some() {
+                self.reload(&dest).await;
+            }
+            return Err(e);
+        }
+
+        let result = self.activate(&dest.join(MANIFEST_FILE)).await;
+        // 只在为空时删除
+        let _ = std::fs::remove_dir(root.join(STAGING_DIR));
+        match result {
+            Ok(id) => {
+                self.registry.set_installed(&id, &dest);
+                if backup.exists()
+                    && let Err(e) = std::fs::remove_dir_all(&backup)
+                {
+                    warn!("failed to remove backup {backup:?}: {e}");
+                }
+                let _ = std::fs::remove_dir(root.join(BACKUP_DIR));
+                info!("installed plugin {} {}", manifest.name, manifest.version);
+                let payload =
+                    json!({ "id": id, "name": manifest.name, "version": manifest.version });
+                self.publish_host(TOPIC_PLUGIN_INSTALLED, payload);
+                Ok(id)
+            }
+            Err(e) => {
+                warn!("install {} failed: {e}, rollback", manifest.name);
+                self.rollback(&dest, &backup, old.is_some()).await;
+                let _ = std::fs::remove_dir(root.join(BACKUP_DIR));
+                Err(e)
+            }
+        }
+    }
+
+    /// 新版本加载失败后删除新版本、从备份还原旧版本，原先已加载的重新加载；失败时只记录日志
+    async fn rollback(&self, dest: &Path, backup: &Path, reload: bool) {
+        if let Err(e) = std::fs::remove_dir_all(dest) {
+            return warn!("rollback failed, cannot remove {dest:?}: {e}");
+        }
+        if backup.exists()
+            && let Err(e) = std::fs::rename(backup, dest)
+        {
+            return warn!("rollback failed, old version is kept in {backup:?}: {e}");
+        }
+        if reload {
+            self.reload(dest).await;
+        }
+    }
+
+    /// 重新加载安装目录中的旧版本
+    async fn reload(&self, dest: &Path) {
+        if let Err(e) = self.activate(&dest.join(MANIFEST_FILE)).await {
+            warn!("failed to reload old version from {dest:?}: {e}");
+        }
+    }
+
+    /// 卸载并删除通过插件包安装的插件，只删除安装时创建的文件夹（见 [RegistryEntry::installed](crate::registry::RegistryEntry::installed)）
+    pub async fn uninstall_package(&self, id: &str) -> Result<()> {
+        let entry = self
+            .registry
+            .get(id)
+            .ok_or_else(|| anyhow!("plugin is not registered: {id}"))?;
+        let dir = entry
+            .installed
+            .ok_or_else(|| anyhow!("plugin {id} was not installed from a package"))?;
+        self.unload_plugin(id.to_string())
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+        std::fs::remove_dir_all(&dir)?;
+        info!("uninstalled plugin {id} from {dir:?}");
+        self.publish_host(TOPIC_PLUGIN_UNINSTALLED, json!({ "id": id }));
+        Ok(())
+    }
+
+    /// 安装目录中已存在的插件 id
+    async fn installed(&self, dir: &Path) -> Option<String> {
+        let info = read_manifest(&dir.join(MANIFEST_FILE)).await.ok()?;
+        Some(PluginId::from(&info).to_string())
+    }
+
+    /// 加载安装好的插件；插件已禁用时只更新注册表
+    async fn activate(&self, manifest: &Path) -> Result<String> {
+        let info = read_manifest(manifest).await.map_err(|e| anyhow!("{e}"))?;
+        let id = PluginId::from(&info).to_string();
+        if self.registry.get(&id).is_some_and(|e| !e.enabled) {
+            self.registry.record(&id, info, Some(manifest));
+            return Ok(id);
+        }
+        self.load(info, Some(manifest))
+            .await
+            .map_err(|e| anyhow!("{e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn sha256(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    /// 写入文件及列出它们的校验文件
+    fn package(files: &[(&str, &[u8])]) -> TempDir {
+        let dir = TempDir::new();
+        let mut checksums = String::new();
+        for (path, data) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, data).unwrap();
+        }
+        for (path, data) in files {
+            checksums.push_str(&format!("{}  {path}\n", sha256(data)));
+        }
+        std::fs::write(dir.join(PACKAGE_CHECKSUMS), checksums).unwrap();
+        dir
+    }
+
+    #[test]
+    fn checksums_match() {
+        let dir = package(&[("manifest.json", b"{}"), ("lib/a.so", b"lib")]);
+        assert!(verify_checksums(&dir).is_ok());
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let dir = package(&[("manifest.json", b"{}")]);
+        std::fs::write(dir.join("manifest.json"), b"{\"name\":1}").unwrap();
+        let err = verify_checksums(&dir).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn unlisted_and_missing_files() {
+        let dir = package(&[("manifest.json", b"{}")]);
+        std::fs::write(dir.join("extra.so"), b"extra").unwrap();
+        let err = verify_checksums(&dir).unwrap_err();
+        assert!(err.to_string().contains("not listed"));
+
+        std::fs::remove_file(dir.join("extra.so")).unwrap();
+        std::fs::remove_file(dir.join("manifest.json")).unwrap();
+        let err = verify_checksums(&dir).unwrap_err();
+        assert!(err.to_string().contains("is missing"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn checksums_reject_symlink() {
+        let outside = TempDir::new();
+        std::fs::write(outside.join("secret"), b"secret").unwrap();
+        let dir = package(&[("manifest.json", b"{}")]);
+        std::os::unix::fs::symlink(outside.join("secret"), dir.join("link")).unwrap();
+        let err = verify_checksums(&dir).unwrap_err();
+        assert!(err.to_string().contains("link"));
+    }
+
+    fn tar_with(kind: tar::EntryType) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(2);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "manifest.json", &b"{}"[..])
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "lib.so", "/etc/passwd")
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn paths_stay_inside_package() {
+        let dir = package(&[("lib/a.so", b"lib"), ("ui/index.html", b"ui")]);
+        assert!(inside(&dir, "lib/a.so").is_ok());
+        assert!(inside(&dir, "./ui").is_ok());
+        for path in ["", "..", "../..", "ui/../../x", "/usr/lib/x.so", "missing"] {
+            assert!(inside(&dir, path).is_err(), "{path}");
+        }
+    }
+
+    #[test]
+    fn tar_rejects_links() {
+        for kind in [tar::EntryType::Symlink, tar::EntryType::Link] {
+            let dest = TempDir::new();
+            let archive = tar::Archive::new(std::io::Cursor::new(tar_with(kind)));
+            let err = unpack_tar(archive, &dest).unwrap_err();
+            assert!(err.to_string().contains("link"));
+            assert!(!dest.join("lib.so").exists());
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::AppState;
+use common::random_hex;
+use dashmap::DashMap;
+use libcommon::{debug, warn};
+use pluginmanager::{Caller, PluginError, PluginId, PluginInfo};
+use serde::Serialize;
+use std::{sync::OnceLock, time::Duration};
+use tokio::sync::oneshot;
+use window::{Emitter, WindowId};
+
+/// 宿主方法组：(组名, 方法名)
+const HOST_GROUPS: &[(&str, &[&str])] = &[
+    (
+        host_pluginmanager::HOST_P_M_NAME,
+        host_pluginmanager::HOST_P_M_METHODS,
+    ),
+    (
+        host_config::HOST_CONFIG_NAME,
+        host_config::HOST_CONFIG_METHODS,
+    ),
+    (
+        host_storage::HOST_STORAGE_NAME,
+        host_storage::HOST_STORAGE_METHODS,
+    ),
+    (host_fs::HOST_FS_NAME, host_fs::HOST_FS_METHODS),
+    (host_call::HOST_CALL_NAME, host_call::HOST_CALL_METHODS),
+    (
+        host_events::HOST_EVENTS_NAME,
+        host_events::HOST_EVENTS_METHODS,
+    ),
+];
+
+/// 首次调用时需要用户确认的组或方法；`HostCall` 可调用其他插件导出的任意方法
+const SENSITIVE: &[&str] = &[host_pluginmanager::HOST_P_M_NAME, host_call::HOST_CALL_NAME];
+
+/// 向前端请求确认权限的事件名，事件内容为 [PermissionRequest]
+pub const PERMISSION_EVENT: &str = "permission";
+
+/// 权限确认窗口的页面；插件 UI 与宿主页面同源，确认请求只能在不挂载插件的独立窗口中处理
+pub const PROMPT_PAGE: &str = include_str!("permission.html");
+
+/// 用户未及时确认时视为拒绝（不记录）
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 宿主方法对应的权限，如 `HostPM.scan`；方法不存在时返回 None
+pub fn capability(cmd: &str) -> Option<(&'static str, String)> {
+    HOST_GROUPS
+        .iter()
+        .find(|(_, methods)| methods.contains(&cmd))
+        .map(|(group, _)| (*group, format!("{group}.{cmd}")))
+}
+
+/// 插件是否在描述文件中声明了权限（组名或 `组名.方法名`）
+fn declared(info: &PluginInfo, group: &str, capability: &str) -> bool {
+    info.permissions
+        .iter()
+        .any(|p| p == group || p == capability)
+}
+
+/// 发送到权限确认窗口的请求，该窗口通过 `answerpermission` 命令回复；
+/// `id` 为随机生成，其他窗口和插件 UI 无法猜测
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionRequest {
+    pub id: String,
+    pub plugin: String,
+    pub name: String,
+    pub capability: String,
+}
+
+/// 等待用户确认的权限请求
+#[derive(Default)]
+pub struct Prompts {
+    window: OnceLock<(Emitter, WindowId)>,
+    pending: DashMap<String, oneshot::Sender<bool>>,
+}
+
+impl Prompts {
+    /// 设置询问用户的权限确认窗口（页面为 [PROMPT_PAGE]），有请求时显示、处理完后隐藏；
+    /// 未设置（如无窗口模式）时敏感权限一律拒绝
+    pub fn set_window(&self, emitter: Emitter, window: WindowId) {
+        let _ = self.window.set((emitter, window));
+    }
+
+    /// 权限确认窗口回复确认结果，请求不存在或已超时时返回 false
+    pub fn answer(&self, id: &str, granted: bool) -> bool {
+        match self.pending.remove(id) {
+            Some((_, tx)) => tx.send(granted).is_ok(),
+            None => false,
+        }
+    }
+
+    /// 询问用户，返回 None 表示无法询问或超时
+    async fn ask(&self, plugin: &PluginId, info: &PluginInfo, capability: &str) -> Option<bool> {
+        let (emitter, window) = self.window.get()?;
+        let id = random_hex();
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(id.clone(), tx);
+        let request = PermissionRequest {
+            id: id.clone(),
+            plugin: plugin.to_string(),
+            name: info.name.clone(),
+            capability: capability.to_string(),
+        };
+        emitter.emit_to(
+            window,
+            PERMISSION_EVENT,
+            serde_json::to_value(request).ok()?,
+        );
+        emitter.set_visible(window, true);
+        let result = tokio::time::timeout(PROMPT_TIMEOUT, rx).await;
+        self.pending.remove(&id);
+        if self.pending.is_empty() {
+            emitter.set_visible(window, false);
+        }
+        result.ok()?.ok()
+    }
+}
+
+impl AppState {
+    /// 检查插件能否调用宿主方法，拒绝时返回 [PluginError::PermissionDenied]
+    pub async fn check_permission(&self, caller: &Caller, cmd: &str) -> Result<(), PluginError> {
+        // 未列入权限表的方法一律拒绝，新增的宿主方法须先登记
+        let Some((group, capability)) = capability(cmd) else {
+            return Err(PluginError::PermissionDenied(format!(
+                "unknown host method {cmd}"
+            )));
+        };
+        let plugin = &caller.plugin;
+        let info = self.pm.get(plugin).ok_or(PluginError::PluginNotFound)?;
+        if !declared(&info, group, &capability) {
+            return Err(PluginError::PermissionDenied(format!(
+                "plugin {} did not declare {capability}",
+                info.name
+            )));
+        }
+        if !SENSITIVE.contains(&group) && !SENSITIVE.contains(&capability.as_str()) {
+            return Ok(());
+        }
+
+        if self.confirm(plugin, &info, &capability).await {
+            Ok(())
+        } else {
+            Err(PluginError::PermissionDenied(format!(
+                "{capability} was not granted to plugin {}",
+                info.name
+            )))
+        }
+    }
+
+    /// 用户对权限的确认结果，未确认过时询问用户并记录到注册表
+    pub(crate) async fn confirm(
+        &self,
+        plugin: &PluginId,
+        info: &PluginInfo,
+        capability: &str,
+    ) -> bool {
+        let id = plugin.to_string();
+        if let Some(granted) = self.registry.grant(&id, capability) {
+            return granted;
+        }
+        match self.prompts.ask(plugin, info, capability).await {
+            Some(granted) => {
+                debug!("plugin {} {capability}: granted={granted}", info.name);
+                self.registry.set_grant(&id, capability, granted);
+                granted
+            }
+            None => {
+                warn!(
+                    "permission {capability} for {} was not confirmed",
+                    info.name
+                );
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{AppState, context::read_manifest, discovery::find_manifests};
+use host_events::TOPIC_PLUGINS_RESTORED;
+use host_pluginmanager::PluginRoot;
+use libcommon::{debug, info, warn};
+use pluginmanager::{PluginId, PluginInfo, load_order};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// 插件注册表：记录已加载插件的描述文件路径、启用状态和加载顺序，启动时据此恢复插件
+///
+/// 每次加载和卸载插件时写回文件；未设置文件路径时只保存在内存中
+pub struct Registry {
+    path: Option<PathBuf>,
+    entries: Mutex<Vec<RegistryEntry>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub id: String,
+    /// 插件描述文件；直接通过 [PluginInfo] 加载的插件为空
+    #[serde(default)]
+    pub manifest: Option<PathBuf>,
+    pub enabled: bool,
+    /// 最近一次加载时的插件信息，用于判断插件是否发生变化
+    pub info: PluginInfo,
+    /// 用户对敏感权限的确认结果
+    #[serde(default)]
+    pub grants: BTreeMap<String, bool>,
+    /// 安装插件时创建的文件夹，移除插件时只删除该文件夹；未经安装的插件为空
+    #[serde(default)]
+    pub installed: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryFile {
+    plugins: Vec<RegistryEntry>,
+}
+
+/// 启动时恢复插件的结果
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RestoreReport {
+    /// 按注册表恢复的插件
+    pub loaded: Vec<String>,
+    /// 项目插件目录（`plugins.dirs`）中新发现的插件
+    pub added: Vec<String>,
+    /// 描述文件内容与注册表不一致的插件（已按新内容加载）
+    pub changed: Vec<String>,
+    /// 描述文件或库文件已不存在的插件（已从注册表移除）
+    pub missing: Vec<String>,
+    /// 已禁用而未加载的插件
+    pub disabled: Vec<String>,
+    /// 加载失败的插件及原因
+    pub failed: Vec<(String, String)>,
+}
+
+impl Registry {
+    pub fn open(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_deref()
+            .filter(|p| p.is_file())
+            .and_then(|p| match read(p) {
+                Ok(file) => Some(file.plugins),
+                Err(e) => {
+                    warn!("Failed to read plugin registry {p:?}: {e}, ignore.");
+                    None
+                }
+            })
+            .unwrap_or_default();
+        debug!("plugin registry {path:?}: {} entries", entries.len());
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// 按加载顺序返回所有记录
+    pub fn entries(&self) -> Vec<RegistryEntry> {
+        self.lock().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<RegistryEntry> {
+        self.lock().iter().find(|e| e.id == id).cloned()
+    }
+
+    /// 记录一次加载：已有记录时原位更新（保留顺序和启用状态），否则追加到末尾
+    pub fn record(&self, id: &str, info: PluginInfo, manifest: Option<&Path>) {
+        let manifest = manifest.map(|p| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf()));
+        let mut entries = self.lock();
+        match entries.iter_mut().find(|e| e.id == id) {
+            Some(entry) => {
+                entry.info = info;
+                if manifest.is_some() {
+                    entry.manifest = manifest;
+                }
+            }
+            None => entries.push(RegistryEntry {
+                id: id.to_string(),
+                manifest,
+                enabled: true,
+                info,
+                grants: BTreeMap::new(),
+                installed: None,
+            }),
+        }
+        self.save(&entries);
+    }
+
+    /// 修改启用状态，返回修改后的记录；未登记时返回 None
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> Option<RegistryEntry> {
+        let mut entries = self.lock();
+        let entry = entries.iter_mut().find(|e| e.id == id)?;
+        entry.enabled = enabled;
+        let entry = entry.clone();
+        self.save(&entries);
+        Some(entry)
+    }
+
+    /// 记录安装时创建的文件夹
+    pub fn set_installed(&self, id: &str, dir: &Path) {
+        let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let mut entries = self.lock();
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+            entry.installed = Some(dir);
+            self.save(&entries);
+        }
+    }
+
+    /// 用户对权限的确认结果，未确认过时返回 None
+    pub fn grant(&self, id: &str, capability: &str) -> Option<bool> {
+        self.lock()
+            .iter()
+            .find(|e| e.id == id)
+            .and_then(|e| e.grants.get(capability).copied())
+    }
+
+    pub fn set_grant(&self, id: &str, capability: &str, granted: bool) {
+        let mut entries = self.lock();
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+            entry.grants.insert(capability.to_string(), granted);
+            self.save(&entries);
+        }
+    }
+
+    pub fn remove(&self, id: &str) {
+        let mut entries = self.lock();
+        let len = entries.len();
+        entries.retain(|e| e.id != id);
+        if entries.len() != len {
+            self.save(&entries);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<RegistryEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, entries: &[RegistryEntry]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let file = RegistryFile {
+            plugins: entries.to_vec(),
+        };
+        if let Err(e) = write(path, &file) {
+            warn!("Failed to save plugin registry {path:?}: {e}");
+        }
+    }
+}
+
+fn read(path: &Path) -> anyhow::Result<RegistryFile> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn write(path: &Path, file: &RegistryFile) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // 先写临时文件再重命名，避免中途退出导致注册表损坏
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(file)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+impl AppState {
+    /// 按注册表顺序恢复已启用的插件，再加载项目插件目录（`plugins.dirs`）中尚未登记的插件；
+    /// 用户和系统插件目录中的插件只被发现（见 [AppState::discover_plugins]），
+    /// 通过 `registerplugin` 命令或 `plugins register` 登记后才会加载。
+    /// 被依赖的插件先于依赖它的插件加载
+    pub async fn restore_plugins(&self) -> RestoreReport {
+        let mut report = RestoreReport::default();
+        // (id, 插件信息, 描述文件, 是否为新发现的插件)
+        let mut pending: Vec<(String, PluginInfo, Option<PathBuf>, bool)> = Vec::new();
+        for entry in self.registry.entries() {
+            if !entry.enabled {
+                report.disabled.push(entry.id);
+                continue;
+            }
+            let info = match &entry.manifest {
+                Some(manifest) if !manifest.is_file() => {
+                    warn!("Plugin {} is missing: {manifest:?}", entry.id);
+                    self.registry.remove(&entry.id);
+                    report.missing.push(entry.id);
+                    continue;
+                }
+                Some(manifest) => match read_manifest(manifest).await {
+                    Ok(info) => info,
+                    Err(e) => {
+                        warn!("Failed to read manifest of plugin {}: {e}", entry.id);
+                        report.failed.push((entry.id, e.to_string()));
+                        continue;
+                    }
+                },
+                None if !Path::new(&entry.info.libfile).is_file() => {
+                    warn!("Plugin {} is missing: {}", entry.id, entry.info.libfile);
+                    self.registry.remove(&entry.id);
+                    report.missing.push(entry.id);
+                    continue;
+                }
+                None => entry.info.clone(),
+            };
+            if info.version != entry.info.version || info.libfile != entry.info.libfile {
+                warn!(
+                    "Plugin {} changed: {} -> {}",
+                    entry.id, entry.info.version, info.version
+                );
+                report.changed.push(entry.id.clone());
+            }
+            pending.push((entry.id, info, entry.manifest, false));
+        }
+
+        let config = self.config();
+        for (root, dir) in config.plugin_roots() {
+            if root != PluginRoot::Project {
+                continue;
+            }
+            if !dir.exists() {
+                debug!("plugin dir {dir:?} does not exist, skip");
+                continue;
+            }
+            for manifest in find_manifests(&dir, &config.plugins.discovery) {
+                let info = match read_manifest(&manifest).await {
+                    Ok(info) => info,
+                    Err(e) => {
+                        debug!("Skip {manifest:?}: {e}");
+                        continue;
+                    }
+                };
+                let id = PluginId::from(&info).to_string();
+                if self.registry.get(&id).is_some() || pending.iter().any(|p| p.0 == id) {
+                    continue;
+                }
+                pending.push((id, info, Some(manifest), true));
+            }
+        }
+
+        let infos: Vec<PluginInfo> = pending.iter().map(|p| p.1.clone()).collect();
+        let (order, cyclic) = load_order(&infos);
+        for i in cyclic {
+            let (id, info, ..) = &pending[i];
+            warn!("Failed to restore plugin {}: dependency cycle", info.name);
+            report
+                .failed
+                .push((id.clone(), "dependency cycle".to_string()));
+        }
+        for i in order {
+            let (id, info, manifest, added) = &pending[i];
+            match self.load(info.clone(), manifest.as_deref()).await {
+                Ok(id) if *added => report.added.push(id),
+                Ok(id) => report.loaded.push(id),
+                Err(e) => {
+                    warn!("Failed to restore plugin {id}: {e}");
+                    report.failed.push((id.clone(), e.to_string()));
+                }
+            }
+        }
+        info!(
+            "restored plugins: {} loaded, {} added, {} changed, {} missing, {} disabled, {} failed",
+            report.loaded.len(),
+            report.added.len(),
+            report.changed.len(),
+            report.missing.len(),
+            report.disabled.len(),
+            report.failed.len()
+        );
+        *self.restored.lock().unwrap_or_else(|e| e.into_inner()) = report.clone();
+        self.publish_host(TOPIC_PLUGINS_RESTORED, json!(report));
+        report
+    }
+
+    /// 最近一次恢复插件的结果，见 [AppState::restore_plugins]
+    pub fn restore_report(&self) -> RestoreReport {
+        self.restored
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
@@ -0,0 +1,96 @@
+use super::{coded_response, error_response};
+use crate::AppState;
+use axum::{
+    Json, Router,
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
+    routing::any,
+};
+use libcommon::{trace, warn};
+use pluginmanager::{
+    PluginError, PluginId,
+    plugin::{Binary, Value},
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// 插件 HTTP 接口：`/plugins/{id}/api/{method}`
+///
+/// 请求体（JSON）作为参数；无请求体时使用查询参数，均无时为 null
+pub(crate) fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/plugins/{id}/api/{method}", any(call_plugin))
+        .with_state(state)
+}
+
+async fn call_plugin(
+    State(state): State<Arc<AppState>>,
+    Path((id, method)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    body: Bytes,
+) -> Response {
+    let params = if !body.is_empty() {
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(v) => v,
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+        }
+    } else if !query.is_empty() {
+        Value::Object(
+            query
+                .into_iter()
+                .map(|(k, v)| (k, Value::String(v)))
+                .collect(),
+        )
+    } else {
+        Value::Null
+    };
+
+    let plugin_id = PluginId(id.into());
+    match state.call_plugin(&plugin_id, &method, params).await {
+        Ok(value) => {
+            trace!("Plugin api({plugin_id}/{method}) ok");
+            value_response(value)
+        }
+        Err(e) => {
+            warn!("Plugin api({plugin_id}/{method}) failed: {e}");
+            let error = e.downcast_ref::<PluginError>();
+            coded_response(
+                status(&*e),
+                e.to_string(),
+                error.and_then(PluginError::code),
+            )
+        }
+    }
+}
+
+/// 调用失败的状态码：参数无效（插件的 [call](pluginmanager::plugin::call) 宏解析参数失败）为 400，
+/// 权限不足为 403，插件不存在为 404，插件之间循环调用或嵌套过深为 508，调用被拒绝为 429
+fn status(e: &(dyn std::error::Error + 'static)) -> StatusCode {
+    if let Some(e) = e.downcast_ref::<PluginError>() {
+        return match e {
+            PluginError::PluginNotFound => StatusCode::NOT_FOUND,
+            PluginError::Busy(_) => StatusCode::TOO_MANY_REQUESTS,
+            PluginError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            PluginError::CallCycle(_) | PluginError::CallTooDeep(_) => StatusCode::LOOP_DETECTED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+    }
+    match e.downcast_ref::<std::io::Error>() {
+        Some(e) if e.kind() == std::io::ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+        _ if e.is::<serde_json::Error>() => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// 二进制返回值按原始字节和 MIME 返回，其余以 JSON 返回
+fn value_response(value: Value) -> Response {
+    match Binary::from_value(&value) {
+        Some(bin) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, bin.mime)
+            .body(Body::from(bin.data))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        None => Json(value).into_response(),
+    }
+}
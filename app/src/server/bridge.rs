@@ -0,0 +1,172 @@
+use super::{coded_response, error_response};
+use crate::AppState;
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{
+        Path, Request, State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    http::{
+        StatusCode,
+        header::{AUTHORIZATION, CONTENT_TYPE},
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use futures_util::{SinkExt, StreamExt};
+use libcommon::{debug, trace, warn};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use window::{BridgeError, BridgeHandlers, RawMessage, Reply, Source, WindowState};
+
+/// 应用状态、命令表，以及服务器停止的通知（见 [serve_socket]）
+type BridgeState = (
+    Arc<AppState>,
+    BridgeHandlers<AppState>,
+    watch::Receiver<bool>,
+);
+
+/// 将 [bridge](window::bridge) 命令表挂载到 HTTP 上，无窗口时也可调用
+///
+/// - `POST /bridge/{command}`：请求体为命令参数（JSON），返回命令结果
+/// - `GET /bridge/ws`：WebSocket，收发与窗口 IPC 相同的 `IpcReq`/`IpcResp` 文本帧（批量请求为数组）
+/// - `GET /bridge/blob/{id}`：读取 WebSocket 响应中 `blob` 对应的二进制数据，只能读取一次
+///
+/// 请求需携带本次启动的令牌（见 [Server::save_token](super::Server::save_token)）：
+/// `Authorization: Bearer <token>` 请求头，或无法设置请求头时（如浏览器的 WebSocket）使用 `?token=<token>`。
+/// 只能调用开放给窗口之外通道的命令（见 [window::Access::Any]），其余命令经由此处调用时返回 403
+pub(crate) fn router(
+    state: Arc<AppState>,
+    handlers: BridgeHandlers<AppState>,
+    token: Arc<str>,
+    closing: watch::Receiver<bool>,
+) -> Router {
+    Router::new()
+        .route("/bridge/ws", get(websocket))
+        .route("/bridge/blob/{id}", get(blob))
+        .route("/bridge/{command}", post(call))
+        .with_state((state, handlers, closing))
+        .layer(middleware::from_fn_with_state(token, authorize))
+}
+
+/// 校验令牌，防止网页跨站调用本机的 bridge 接口
+async fn authorize(State(token): State<Arc<str>>, req: Request, next: Next) -> Response {
+    let header = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let query = req
+        .uri()
+        .query()
+        .and_then(|q| q.split('&').find_map(|pair| pair.strip_prefix("token=")));
+    match header.or(query) {
+        Some(given) if constant_eq(given.as_bytes(), token.as_bytes()) => next.run(req).await,
+        _ => error_response(StatusCode::UNAUTHORIZED, "invalid bridge token".to_string()),
+    }
+}
+
+/// 比较耗时与内容无关，避免按响应时间猜测令牌
+fn constant_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn call(
+    State((state, handlers, _)): State<BridgeState>,
+    Path(command): Path<String>,
+    body: Bytes,
+) -> Response {
+    let payload = if body.is_empty() {
+        None
+    } else {
+        match serde_json::from_slice::<RawMessage>(&body) {
+            Ok(raw) => Some(raw),
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+        }
+    };
+    trace!("Bridge http request: {command}");
+    match handlers
+        .call(&command, payload, WindowState(state), &Source::Remote)
+        .await
+    {
+        Ok(Reply::Json(value)) => Json(value).into_response(),
+        Ok(Reply::Binary { mime, data }) => ([(CONTENT_TYPE, mime)], data).into_response(),
+        Err(e @ BridgeError::NoHandler(_)) => error_response(StatusCode::NOT_FOUND, e.to_string()),
+        Err(e @ BridgeError::Forbidden(_)) => error_response(StatusCode::FORBIDDEN, e.to_string()),
+        Err(e) => {
+            // 插件调用被并发限制拒绝
+            let status = match e.code() {
+                Some("busy") => StatusCode::TOO_MANY_REQUESTS,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            coded_response(status, e.to_string(), e.code())
+        }
+    }
+}
+
+async fn blob(State((_, handlers, _)): State<BridgeState>, Path(id): Path<String>) -> Response {
+    match handlers.blobs().take(&id) {
+        Some((mime, data)) => ([(CONTENT_TYPE, mime)], data).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, format!("blob {id} not found")),
+    }
+}
+
+async fn websocket(
+    State((state, handlers, closing)): State<BridgeState>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(move |socket| serve_socket(socket, state, handlers, closing))
+}
+
+/// 服务器停止时向客户端发送关闭帧并结束连接，不等待客户端断开
+async fn serve_socket(
+    socket: WebSocket,
+    state: Arc<AppState>,
+    handlers: BridgeHandlers<AppState>,
+    mut closing: watch::Receiver<bool>,
+) {
+    debug!("Bridge websocket connected");
+    let (mut sender, mut receiver) = socket.split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+
+    // 响应可能乱序完成，统一由写任务发送
+    let writer = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let close = matches!(msg, Message::Close(_));
+            if sender.send(msg).await.is_err() || close {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => msg,
+            _ = closing.wait_for(|closing| *closing) => {
+                let _ = tx.send(Message::Close(None));
+                break;
+            }
+        };
+        let text = match msg {
+            Some(Ok(Message::Text(text))) => text.to_string(),
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+            Some(Ok(_)) => continue,
+        };
+        let (tx, state, handlers) = (tx.clone(), state.clone(), handlers.clone());
+        tokio::spawn(async move {
+            if let Some(resp) = handlers
+                .dispatch(&text, WindowState(state), &Source::Remote)
+                .await
+                && tx.send(Message::Text(resp.into())).is_err()
+            {
+                warn!("Bridge websocket closed before response was sent");
+            }
+        });
+    }
+
+    drop(tx);
+    let _ = writer.await;
+    debug!("Bridge websocket disconnected");
+}
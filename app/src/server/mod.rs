@@ -1,19 +1,27 @@
+mod api;
+mod bridge;
+mod proxy;
+
+use crate::AppState;
 use axum::{
-    Router,
+    Json, Router,
     body::Body,
+    extract::OriginalUri,
     http::{Request, StatusCode, header::CONTENT_TYPE},
     response::{IntoResponse, Response},
     routing::get,
 };
 use dashmap::DashMap;
-use libcommon::{Result, debug};
 #[cfg(any(not(debug_assertions), feature = "use-embed"))]
-use libcommon::{trace, warn};
+use libcommon::warn;
+use libcommon::{Result, debug, trace};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tower_http::{
     cors::{Any, CorsLayer},
     services::ServeDir,
 };
+use window::BridgeHandlers;
 
 #[cfg(any(not(debug_assertions), feature = "use-embed"))]
 use rust_embed::RustEmbed;
@@ -24,8 +32,13 @@ pub type RouteTable = Arc<DashMap<String, RouteSource>>;
 #[derive(Debug, Clone)]
 pub struct Server {
     pub port: u16,
+    pub bind: String,
     pub static_routes: Vec<StaticRoute>, // 静态宿主路由（编译时确定）
     pub plugin_routes: RouteTable,       // 动态插件路由表
+    pub proxy_client: reqwest::Client,   // 反向代理客户端
+    pub frontend: bool,                  // 是否需要宿主前端（无窗口模式不需要）
+    pub metrics: bool,                   // 是否提供 Prometheus 指标接口
+    pub token: Arc<str>,                 // bridge HTTP 接口的令牌，每次启动随机生成
 }
 
 #[derive(Debug, Clone)]
@@ -44,13 +57,17 @@ const DEV_SERVER_URL: &str = "http://localhost:3000/";
 pub struct ServerAssets;
 
 /// 数据来源
+///
+/// `spa` 为 true 时，找不到文件且无扩展名的请求（前端路由）回退到 `index.html`
 #[derive(Debug, Clone)]
 pub enum RouteSource {
     /// 文件系统来源（用于插件 UI）
-    File { path: String },
+    File { path: String, spa: bool },
+    /// 反向代理来源（用于 uiurl 为 http(s) dev server 的插件 UI）
+    Proxy { upstream: String },
     /// 嵌入资源来源（用于宿主前端）
     #[cfg(any(not(debug_assertions), feature = "use-embed"))]
-    Embedded,
+    Embedded { spa: bool },
 }
 
 impl Server {
@@ -60,7 +77,7 @@ impl Server {
             #[cfg(any(not(debug_assertions), feature = "use-embed"))]
             {
                 // 宿主前端使用嵌入资源
-                vec![StaticRoute::new_embedded("/")]
+                vec![StaticRoute::new_embedded("/").with_spa(true)]
             }
             #[cfg(all(debug_assertions, not(feature = "use-embed")))]
             {
@@ -70,11 +87,48 @@ impl Server {
         };
         Self {
             port,
+            bind: "127.0.0.1".to_string(),
             static_routes,
             plugin_routes: Arc::new(DashMap::new()),
+            proxy_client: proxy::client(),
+            frontend: true,
+            metrics: false,
+            token: common::random_hex().into(),
         }
     }
 
+    /// 设置监听地址
+    pub fn with_bind(mut self, bind: impl Into<String>) -> Self {
+        self.bind = bind.into();
+        self
+    }
+
+    /// 设置是否需要宿主前端；无窗口模式下不启动前端 dev server
+    pub fn with_frontend(mut self, frontend: bool) -> Self {
+        self.frontend = frontend;
+        self
+    }
+
+    /// 设置是否在 `/metrics` 提供 Prometheus 文本格式的调用统计
+    pub fn with_metrics(mut self, metrics: bool) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// 将 bridge 令牌写入文件（仅当前用户可读），供本机的其他程序调用 bridge HTTP 接口
+    pub fn save_token(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        std::io::Write::write_all(&mut options.open(path)?, self.token.as_bytes())?;
+        debug!("bridge token saved to {path:?}");
+        Ok(())
+    }
+
     /// 返回主窗口应加载的 URL（开发模式返回 dev server，发布模式返回内嵌服务器地址）
     pub fn window_url(&self) -> String {
         #[cfg(any(not(debug_assertions), feature = "use-embed"))]
@@ -92,11 +146,19 @@ impl Server {
         format!("http://127.0.0.1:{}", self.port)
     }
 
-    /// 动态添加插件路由
-    pub fn add_plugin_route(&self, plugin_id: &str, base_dir: impl Into<String>) {
+    /// 返回插件 UI 的同源访问地址
+    pub fn plugin_url(&self, plugin_id: &str) -> String {
+        format!("{}/plugins/{plugin_id}/", self.server_url())
+    }
+
+    /// 动态添加插件路由；`uiurl` 为 http(s) 地址时反向代理到该地址，否则作为文件夹服务
+    pub fn add_plugin_route(&self, plugin_id: &str, uiurl: impl Into<String>, spa: bool) {
         let url_route = format!("/plugins/{plugin_id}");
-        let source = RouteSource::File {
-            path: base_dir.into(),
+        let uiurl = uiurl.into();
+        let source = if uiurl.starts_with("http://") || uiurl.starts_with("https://") {
+            RouteSource::Proxy { upstream: uiurl }
+        } else {
+            RouteSource::File { path: uiurl, spa }
         };
         debug!("Adding plugin route: {url_route} -> {source:?}");
         self.plugin_routes.insert(url_route, source);
@@ -110,16 +172,36 @@ impl Server {
     }
 
     /// 启动服务器
-    pub async fn run(mut self) -> Result<()> {
+    pub async fn run(self, state: Arc<AppState>, handlers: BridgeHandlers<AppState>) -> Result<()> {
+        self.run_until(state, handlers, std::future::pending())
+            .await
+    }
+
+    /// 启动服务器，`shutdown` 完成后停止接收新连接、关闭 bridge WebSocket 并等待已有请求结束
+    pub async fn run_until(
+        mut self,
+        state: Arc<AppState>,
+        handlers: BridgeHandlers<AppState>,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<()> {
         #[cfg(all(debug_assertions, not(feature = "use-embed")))]
-        self.start_dev_server();
+        if self.frontend {
+            self.start_dev_server();
+        }
 
         let mut app = Router::new().route("/health", get(health_check));
+        if self.metrics {
+            let state = state.clone();
+            app = app.route("/metrics", get(move || metrics(state.clone())));
+        }
+
+        // 插件 HTTP 接口，优先于插件静态路由匹配
+        app = app.merge(api::router(state.clone()));
 
         // ---------- 1. 静态宿主路由 ----------
         for route in &self.static_routes {
             match &route.source {
-                RouteSource::File { path } => {
+                RouteSource::File { path, spa: false } => {
                     let serve_dir = ServeDir::new(path).not_found_service(get(not_found));
                     if route.url_route == "/" {
                         app = app.fallback_service(serve_dir);
@@ -128,9 +210,41 @@ impl Server {
                     }
                     debug!("  [Static FS] {} -> {}", route.url_route, path);
                 }
+                RouteSource::File { path, spa: true } => {
+                    let base_dir = path.clone();
+                    let spa_router = Router::new().fallback(move |req: Request<Body>| {
+                        let base_dir = base_dir.clone();
+                        async move {
+                            let relative = req.uri().path().trim_start_matches('/').to_string();
+                            serve_file(&base_dir, &relative, true).await
+                        }
+                    });
+                    if route.url_route == "/" {
+                        app = app.merge(spa_router);
+                    } else {
+                        app = app.nest(&route.url_route, spa_router);
+                    }
+                    debug!("  [Static FS SPA] {} -> {}", route.url_route, path);
+                }
+                RouteSource::Proxy { upstream } => {
+                    let (client, target) = (self.proxy_client.clone(), upstream.clone());
+                    let proxy_router = Router::new().fallback(move |req: Request<Body>| {
+                        let (client, upstream) = (client.clone(), target.clone());
+                        async move {
+                            let relative = req.uri().path().trim_start_matches('/').to_string();
+                            proxy::forward(&client, &upstream, &relative, req).await
+                        }
+                    });
+                    if route.url_route == "/" {
+                        app = app.merge(proxy_router);
+                    } else {
+                        app = app.nest(&route.url_route, proxy_router);
+                    }
+                    debug!("  [Static Proxy] {} -> {}", route.url_route, upstream);
+                }
                 #[cfg(any(not(debug_assertions), feature = "use-embed"))]
-                RouteSource::Embedded => {
-                    let embedded_router = Self::embedded_router();
+                RouteSource::Embedded { spa } => {
+                    let embedded_router = Self::embedded_router(*spa);
                     if route.url_route == "/" {
                         app = app.merge(embedded_router);
                     } else {
@@ -143,17 +257,30 @@ impl Server {
 
         // ---------- 2. 插件动态路由 ----------
         let plugin_routes = self.plugin_routes.clone();
+        let client = self.proxy_client.clone();
         let plugin_fallback = Router::new()
-            .fallback(move |req| serve_plugin(req, plugin_routes.clone()))
+            .fallback(move |req| serve_plugin(req, plugin_routes.clone(), client.clone()))
             .layer(CorsLayer::new().allow_origin(Any));
         app = app.nest("/plugins", plugin_fallback);
 
         app = app.layer(CorsLayer::new().allow_origin(Any));
+        // bridge 命令的 HTTP/WebSocket 接口，需携带令牌且不允许跨域
+        // WebSocket 连接不会自行结束，停止时通知其关闭，否则会一直等待
+        let (closing_tx, closing) = tokio::sync::watch::channel(false);
+        app = app.merge(bridge::router(state, handlers, self.token.clone(), closing));
+        let shutdown = async move {
+            shutdown.await;
+            let _ = closing_tx.send(true);
+        };
 
-        let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", self.port)).await?;
+        let listener =
+            tokio::net::TcpListener::bind(format!("{}:{}", self.bind, self.port)).await?;
         self.port = listener.local_addr()?.port();
         debug!("Starting static file server at {}", self.server_url());
-        axum::serve(listener, app).await?;
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown)
+            .await?;
+        debug!("Static file server stopped");
         Ok(())
     }
 
@@ -188,14 +315,23 @@ impl Server {
     }
 
     #[cfg(any(not(debug_assertions), feature = "use-embed"))]
-    fn embedded_router() -> Router {
-        Router::new().fallback(move |req| serve_embedded::<ServerAssets>(req))
+    fn embedded_router(spa: bool) -> Router {
+        Router::new().fallback(move |req| serve_embedded::<ServerAssets>(req, spa))
     }
 }
 
 // ---------- 插件动态服务 ----------
-async fn serve_plugin(req: Request<Body>, routes: RouteTable) -> impl IntoResponse {
-    let path = req.uri().path().to_string();
+async fn serve_plugin(
+    req: Request<Body>,
+    routes: RouteTable,
+    client: reqwest::Client,
+) -> impl IntoResponse {
+    // nest 会去掉 "/plugins" 前缀，使用原始路径匹配路由表
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
     debug!("Plugin request: {}", path);
 
     // 寻找最长匹配前缀（键如 "/plugins/foo"）
@@ -213,9 +349,13 @@ async fn serve_plugin(req: Request<Body>, routes: RouteTable) -> impl IntoRespon
     let relative = path.strip_prefix(&prefix).unwrap().trim_start_matches('/');
 
     match source {
-        RouteSource::File { path: base_dir } => serve_file(&base_dir, relative).await,
+        RouteSource::File {
+            path: base_dir,
+            spa,
+        } => serve_file(&base_dir, relative, spa).await,
+        RouteSource::Proxy { upstream } => proxy::forward(&client, &upstream, relative, req).await,
         #[cfg(any(not(debug_assertions), feature = "use-embed"))]
-        RouteSource::Embedded => {
+        RouteSource::Embedded { .. } => {
             // 理论上插件不应使用 Embedded，但可留作扩展
             StatusCode::NOT_IMPLEMENTED.into_response()
         }
@@ -223,9 +363,7 @@ async fn serve_plugin(req: Request<Body>, routes: RouteTable) -> impl IntoRespon
 }
 
 /// 从文件系统服务插件文件
-async fn serve_file(base_dir: &str, relative: &str) -> Response {
-    use std::path::PathBuf;
-
+async fn serve_file(base_dir: &str, relative: &str, spa: bool) -> Response {
     let full_path = PathBuf::from(base_dir).join(relative);
     // 安全检查：防止目录穿越
     if !full_path.starts_with(base_dir) {
@@ -238,24 +376,44 @@ async fn serve_file(base_dir: &str, relative: &str) -> Response {
         full_path
     };
 
-    match tokio::fs::read(&file_path).await {
-        Ok(content) => {
-            let mime = mime_guess::from_path(&file_path).first_or_octet_stream();
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(CONTENT_TYPE, mime.as_ref())
-                .body(Body::from(content))
-                .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
-        }
-        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    if let Some(resp) = read_file(&file_path).await {
+        return resp;
+    }
+    // 前端路由回退
+    if spa
+        && is_spa_route(relative)
+        && let Some(resp) = read_file(&PathBuf::from(base_dir).join("index.html")).await
+    {
+        trace!("SPA fallback: {relative} -> index.html");
+        return resp;
     }
+    StatusCode::NOT_FOUND.into_response()
+}
+
+async fn read_file(file_path: &Path) -> Option<Response> {
+    let content = tokio::fs::read(file_path).await.ok()?;
+    let mime = mime_guess::from_path(file_path).first_or_octet_stream();
+    let resp = Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, mime.as_ref())
+        .body(Body::from(content))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    Some(resp)
+}
+
+/// 无扩展名的路径视为前端路由（如 `/settings`），带扩展名的视为静态资源
+fn is_spa_route(relative: &str) -> bool {
+    Path::new(relative).extension().is_none()
 }
 
 impl StaticRoute {
     pub fn new_file(url_route: &str, path: impl Into<String>) -> Self {
         Self {
             url_route: url_route.into(),
-            source: RouteSource::File { path: path.into() },
+            source: RouteSource::File {
+                path: path.into(),
+                spa: false,
+            },
         }
     }
 
@@ -263,22 +421,37 @@ impl StaticRoute {
     pub fn new_embedded(url_route: &str) -> Self {
         Self {
             url_route: url_route.into(),
-            source: RouteSource::Embedded,
+            source: RouteSource::Embedded { spa: false },
         }
     }
+
+    /// 开启/关闭前端路由回退
+    pub fn with_spa(mut self, enable: bool) -> Self {
+        match &mut self.source {
+            RouteSource::File { spa, .. } => *spa = enable,
+            RouteSource::Proxy { .. } => {}
+            #[cfg(any(not(debug_assertions), feature = "use-embed"))]
+            RouteSource::Embedded { spa } => *spa = enable,
+        }
+        self
+    }
 }
 
 /// 嵌入资源处理器（用于宿主前端）
 #[cfg(any(not(debug_assertions), feature = "use-embed"))]
-async fn serve_embedded<E: RustEmbed>(req: Request<Body>) -> impl IntoResponse {
+async fn serve_embedded<E: RustEmbed>(req: Request<Body>, spa: bool) -> impl IntoResponse {
     let path = req.uri().path();
     let relative_path = path.trim_start_matches('/');
 
-    let file_path = if relative_path.is_empty() {
+    let mut file_path = if relative_path.is_empty() {
         "index.html"
     } else {
         relative_path
     };
+    // 前端路由回退
+    if spa && E::get(file_path).is_none() && is_spa_route(file_path) {
+        file_path = "index.html";
+    }
 
     let resp = match E::get(file_path) {
         Some(content) => {
@@ -300,6 +473,28 @@ async fn serve_embedded<E: RustEmbed>(req: Request<Body>) -> impl IntoResponse {
     resp
 }
 
+async fn metrics(state: Arc<AppState>) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.prometheus(),
+    )
+}
+
+/// JSON 错误响应 `{ error }`
+fn error_response(status: StatusCode, error: String) -> Response {
+    coded_response(status, error, None)
+}
+
+/// 带错误码的 JSON 错误响应 `{ error, code }`，错误码见 [PluginError::code](pluginmanager::PluginError::code)
+/// 和 [window::CommandError]
+fn coded_response(status: StatusCode, error: String, code: Option<&str>) -> Response {
+    let body = match code {
+        Some(code) => serde_json::json!({ "error": error, "code": code }),
+        None => serde_json::json!({ "error": error }),
+    };
+    (status, Json(body)).into_response()
+}
+
 async fn health_check() -> &'static str {
     "ok"
 }
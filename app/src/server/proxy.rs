@@ -0,0 +1,97 @@
+use axum::{
+    body::Body,
+    http::{
+        HeaderMap, Request, StatusCode, Version,
+        header::{CONNECTION, HOST, TRANSFER_ENCODING, UPGRADE},
+    },
+    response::{IntoResponse, Response},
+};
+use hyper::upgrade::OnUpgrade;
+use hyper_util::rt::TokioIo;
+use libcommon::{Result, debug, trace, warn};
+
+/// 创建反向代理使用的客户端（不跟随重定向，交由浏览器处理）
+pub(crate) fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap_or_default()
+}
+
+/// 将请求转发到插件的 dev server，支持 WebSocket 升级（用于 HMR）
+pub(crate) async fn forward(
+    client: &reqwest::Client,
+    upstream: &str,
+    relative: &str,
+    mut req: Request<Body>,
+) -> Response {
+    let query = req
+        .uri()
+        .query()
+        .map(|q| format!("?{q}"))
+        .unwrap_or_default();
+    let url = format!("{}/{relative}{query}", upstream.trim_end_matches('/'));
+    let on_upgrade = is_upgrade(req.headers()).then(|| hyper::upgrade::on(&mut req));
+    trace!("Proxy request: {} -> {url}", req.uri());
+
+    let (parts, body) = req.into_parts();
+    let mut headers = parts.headers;
+    headers.remove(HOST);
+    let mut builder = client.request(parts.method, &url).headers(headers);
+    builder = if on_upgrade.is_some() {
+        builder.version(Version::HTTP_11)
+    } else {
+        builder.body(reqwest::Body::wrap_stream(body.into_data_stream()))
+    };
+
+    let upstream_resp = match builder.send().await {
+        Ok(resp) => resp,
+        Err(e) => {
+            warn!("Proxy to {url} failed: {e}");
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+
+    let status = upstream_resp.status();
+    let mut resp_headers = upstream_resp.headers().clone();
+    if status == StatusCode::SWITCHING_PROTOCOLS {
+        let Some(on_upgrade) = on_upgrade else {
+            return StatusCode::BAD_GATEWAY.into_response();
+        };
+        tokio::spawn(async move {
+            match tunnel(on_upgrade, upstream_resp).await {
+                Ok(_) => debug!("Proxy tunnel to {url} closed"),
+                Err(e) => warn!("Proxy tunnel to {url} failed: {e}"),
+            }
+        });
+        return build_response(status, resp_headers, Body::empty());
+    }
+
+    // 响应体以流的形式重新编码，去掉逐跳头
+    resp_headers.remove(TRANSFER_ENCODING);
+    resp_headers.remove(CONNECTION);
+    build_response(
+        status,
+        resp_headers,
+        Body::from_stream(upstream_resp.bytes_stream()),
+    )
+}
+
+/// 双向转发升级后的连接
+async fn tunnel(client: OnUpgrade, upstream: reqwest::Response) -> Result<()> {
+    let mut upstream = upstream.upgrade().await?;
+    let mut client = TokioIo::new(client.await?);
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+fn is_upgrade(headers: &HeaderMap) -> bool {
+    headers.contains_key(UPGRADE)
+}
+
+fn build_response(status: StatusCode, headers: HeaderMap, body: Body) -> Response {
+    let mut resp = Response::new(body);
+    *resp.status_mut() = status;
+    *resp.headers_mut() = headers;
+    resp
+}
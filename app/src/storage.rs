@@ -0,0 +1,289 @@
+use crate::AppState;
+use anyhow::{Result, anyhow};
+use host_storage::{HostStorage, StorageOp};
+use libcommon::debug;
+use pluginmanager::{
+    Caller,
+    plugin::{PluginResult, Value, async_trait},
+};
+use redb::{Database, ReadableTable, TableDefinition, TableError};
+use std::{path::PathBuf, sync::Arc};
+use tokio::sync::OnceCell;
+
+/// 键值数据库文件名（位于存储文件夹）
+const DB_FILE: &str = "kv.redb";
+/// 插件数据文件夹的父文件夹（位于存储文件夹）
+const DATA_DIR: &str = "plugins";
+/// 各插件已用容量
+const USAGE: TableDefinition<&str, u64> = TableDefinition::new("usage");
+
+/// 插件数据存储
+///
+/// 键值数据保存在内嵌数据库中，每个插件一张表；另为每个插件提供数据文件夹和缓存文件夹。
+/// 以插件名作为命名空间，插件升级后数据保留。数据库在首次使用时打开，
+/// 因此应用运行期间，命令行中只有用到存储的命令会因数据库被占用而失败。
+/// 数据库的读写（含提交时的 fsync）在阻塞线程中执行，不占用异步工作线程
+pub struct Storage {
+    dir: PathBuf,
+    cache: PathBuf,
+    quota: u64,
+    db: OnceCell<Arc<Database>>,
+}
+
+impl Storage {
+    pub fn new(dir: PathBuf, cache: PathBuf, quota: u64) -> Self {
+        Self {
+            dir,
+            cache,
+            quota,
+            db: OnceCell::new(),
+        }
+    }
+
+    async fn db(&self) -> Result<Arc<Database>> {
+        let db = self
+            .db
+            .get_or_try_init(|| async {
+                let dir = self.dir.clone();
+                tokio::task::spawn_blocking(move || {
+                    std::fs::create_dir_all(&dir)?;
+                    let path = dir.join(DB_FILE);
+                    debug!("open storage {path:?}");
+                    Database::create(&path)
+                        .map(Arc::new)
+                        .map_err(|e| anyhow!("failed to open {path:?}: {e}"))
+                })
+                .await?
+            })
+            .await?;
+        Ok(db.clone())
+    }
+
+    /// 在阻塞线程中执行数据库操作
+    async fn blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+    {
+        let db = self.db().await?;
+        tokio::task::spawn_blocking(move || f(&db)).await?
+    }
+
+    pub async fn get(&self, ns: &str, key: &str) -> Result<Option<Value>> {
+        let (name, key) = (table_name(ns), key.to_string());
+        self.blocking(move |db| {
+            let txn = db.begin_read()?;
+            let table = match txn.open_table(table(&name)) {
+                Ok(table) => table,
+                Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            let Some(value) = table.get(key.as_str())? else {
+                return Ok(None);
+            };
+            Ok(Some(serde_json::from_slice(value.value())?))
+        })
+        .await
+    }
+
+    /// 以 `prefix` 开头的键，按字典序排列
+    pub async fn keys(&self, ns: &str, prefix: &str) -> Result<Vec<String>> {
+        let (name, prefix) = (table_name(ns), prefix.to_string());
+        self.blocking(move |db| {
+            let txn = db.begin_read()?;
+            let table = match txn.open_table(table(&name)) {
+                Ok(table) => table,
+                Err(TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+                Err(e) => return Err(e.into()),
+            };
+            let mut keys = Vec::new();
+            for item in table.range(prefix.as_str()..)? {
+                let (key, _) = item?;
+                let key = key.value();
+                if !key.starts_with(prefix.as_str()) {
+                    break;
+                }
+                keys.push(key.to_string());
+            }
+            Ok(keys)
+        })
+        .await
+    }
+
+    /// 在一个事务中执行所有操作，返回被覆盖或删除的键中是否有已存在的
+    pub async fn batch(&self, ns: &str, ops: Vec<StorageOp>) -> Result<bool> {
+        let (ns, quota) = (ns.to_string(), self.quota);
+        self.blocking(move |db| batch(db, &ns, ops, quota)).await
+    }
+
+    /// 插件已用容量（字节）
+    pub async fn usage(&self, ns: &str) -> Result<u64> {
+        let ns = ns.to_string();
+        self.blocking(move |db| {
+            let txn = db.begin_read()?;
+            let usages = match txn.open_table(USAGE) {
+                Ok(table) => table,
+                Err(TableError::TableDoesNotExist(_)) => return Ok(0),
+                Err(e) => return Err(e.into()),
+            };
+            Ok(usages
+                .get(ns.as_str())?
+                .map(|v| v.value())
+                .unwrap_or_default())
+        })
+        .await
+    }
+
+    /// 插件的数据文件夹，不存在时创建
+    pub fn data_dir(&self, ns: &str) -> Result<PathBuf> {
+        let dir = self.dir.join(DATA_DIR).join(dir_name(ns));
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// 插件的缓存文件夹，不存在时创建
+    pub fn cache_dir(&self, ns: &str) -> Result<PathBuf> {
+        let dir = self.cache.join(dir_name(ns));
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// 删除插件的全部键值数据、数据文件夹和缓存文件夹，返回释放的键值容量
+    pub async fn wipe(&self, ns: &str) -> Result<u64> {
+        let dirs = [self.dir.join(DATA_DIR), self.cache.clone()].map(|dir| dir.join(dir_name(ns)));
+        let ns = ns.to_string();
+        self.blocking(move |db| {
+            let txn = db.begin_write()?;
+            txn.delete_table(table(&table_name(&ns)))?;
+            let usage = txn
+                .open_table(USAGE)?
+                .remove(ns.as_str())?
+                .map(|v| v.value())
+                .unwrap_or_default();
+            txn.commit()?;
+            for dir in dirs {
+                if dir.exists() {
+                    std::fs::remove_dir_all(&dir)?;
+                }
+            }
+            debug!("wiped storage of {ns}");
+            Ok(usage)
+        })
+        .await
+    }
+}
+
+/// 在一个事务中执行所有操作，超出配额时放弃
+fn batch(db: &Database, ns: &str, ops: Vec<StorageOp>, quota: u64) -> Result<bool> {
+    let txn = db.begin_write()?;
+    let name = table_name(ns);
+    let (before, usage, existed) = {
+        let mut usages = txn.open_table(USAGE)?;
+        let mut table = txn.open_table(table(&name))?;
+        let before = usages.get(ns)?.map(|v| v.value()).unwrap_or_default();
+        let mut usage = before;
+        let mut existed = false;
+        for op in ops {
+            let (key, old) = match op {
+                StorageOp::Set { key, value } => {
+                    let value = serde_json::to_vec(&value)?;
+                    usage += (key.len() + value.len()) as u64;
+                    let old = table.insert(key.as_str(), value.as_slice())?;
+                    (key, old.map(|v| v.value().len()))
+                }
+                StorageOp::Delete { key } => {
+                    let old = table.remove(key.as_str())?;
+                    (key, old.map(|v| v.value().len()))
+                }
+            };
+            if let Some(len) = old {
+                existed = true;
+                usage = usage.saturating_sub((key.len() + len) as u64);
+            }
+        }
+        usages.insert(ns, usage)?;
+        (before, usage, existed)
+    };
+    // 已超出配额（如调低了配额）时仍允许减少占用
+    if usage > quota && usage > before {
+        txn.abort()?;
+        return Err(anyhow!("storage quota exceeded: {usage} > {} bytes", quota));
+    }
+    txn.commit()?;
+    Ok(existed)
+}
+
+fn table(name: &str) -> TableDefinition<'_, &'static str, &'static [u8]> {
+    TableDefinition::new(name)
+}
+
+fn table_name(ns: &str) -> String {
+    format!("plugin:{ns}")
+}
+
+/// 插件名中不能用于文件夹名的字符替换为`_`
+fn dir_name(ns: &str) -> String {
+    let name: String = ns
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    match name.trim_matches('.') {
+        "" => "_".to_string(),
+        _ => name,
+    }
+}
+
+impl AppState {
+    /// 清除插件的存储数据，`plugin` 为插件 id 或名称（插件可以已被移除），返回释放的键值容量
+    pub async fn wipe_plugin_data(&self, plugin: &str) -> Result<u64> {
+        let name = self
+            .registry
+            .get(plugin)
+            .map(|entry| entry.info.name)
+            .unwrap_or_else(|| plugin.to_string());
+        self.storage.wipe(&name).await
+    }
+}
+
+/// 存储以调用方插件名为命名空间，宿主自身调用时没有命名空间
+fn namespace() -> PluginResult<String> {
+    Caller::current()
+        .map(|caller| caller.name)
+        .ok_or_else(|| "storage is only available to plugins".into())
+}
+
+#[async_trait]
+impl HostStorage for AppState {
+    async fn storage_get(&self, arg: String) -> PluginResult<Option<Value>> {
+        Ok(self.storage.get(&namespace()?, &arg).await?)
+    }
+
+    async fn storage_set(&self, arg: (String, Value)) -> PluginResult<()> {
+        let (key, value) = arg;
+        let op = StorageOp::Set { key, value };
+        self.storage.batch(&namespace()?, vec![op]).await?;
+        Ok(())
+    }
+
+    async fn storage_delete(&self, arg: String) -> PluginResult<bool> {
+        let op = StorageOp::Delete { key: arg };
+        Ok(self.storage.batch(&namespace()?, vec![op]).await?)
+    }
+
+    async fn storage_keys(&self, arg: String) -> PluginResult<Vec<String>> {
+        Ok(self.storage.keys(&namespace()?, &arg).await?)
+    }
+
+    async fn storage_batch(&self, arg: Vec<StorageOp>) -> PluginResult<()> {
+        self.storage.batch(&namespace()?, arg).await?;
+        Ok(())
+    }
+
+    async fn data_dir(&self, _: ()) -> PluginResult<String> {
+        let dir = self.storage.data_dir(&namespace()?)?;
+        Ok(dir.to_string_lossy().to_string())
+    }
+}
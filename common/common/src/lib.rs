@@ -0,0 +1,6 @@
+/// 128 位随机数的十六进制表示，用作令牌、一次性 id 和临时文件名等无法猜测的值
+pub fn random_hex() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("failed to get random bytes");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -1,5 +1,9 @@
 pub use paste::paste;
 
+/// 宿主 API 版本，插件在描述文件的 `host` 中声明所需的版本范围；
+/// 新增组或方法时增加次版本号，修改或移除时增加主版本号
+pub const HOST_API_VERSION: &str = "1.0.0";
+
 #[macro_export]
 macro_rules! define_host_group {
     (
@@ -21,6 +25,11 @@ macro_rules! define_host_group {
                 )*
             }
 
+            /// 组名，插件在描述文件中以 `组名` 或 `组名.方法名` 声明所需权限
+            pub const [<$group:snake:upper _NAME>]: &str = stringify!($group);
+            /// 组内所有方法名
+            pub const [<$group:snake:upper _METHODS>]: &[&str] = &[$(stringify!($method)),*];
+
             // 2. 分发函数
             pub async fn [<try_dispatch_ $group:snake>]<T: $group + ?Sized>(
                 host: &T,
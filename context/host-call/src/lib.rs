@@ -0,0 +1,8 @@
+use context::define_host_group;
+
+define_host_group! {
+    HostCall,
+    /// 调用其他插件导出（见描述文件的 `exports`）的方法，参数为 (插件 id 或名称, 方法名, 参数)；
+    /// 被调用的插件从输入的 `caller` 中得到调用方，插件之间循环调用时返回错误
+    (call_plugin, (String, String, Value), Value),
+}
@@ -0,0 +1,7 @@
+use context::define_host_group;
+
+define_host_group! {
+    HostConfig,
+    /// 读取应用配置（只读）；参数为以`.`分隔的配置项路径，如 `server.port`，为空时返回全部配置
+    (get_config, String, Value),
+}
@@ -0,0 +1,44 @@
+use context::define_host_group;
+
+/// 插件已加载，内容为 `{ id, name, version }`
+pub const TOPIC_PLUGIN_LOADED: &str = "plugin.loaded";
+/// 插件已卸载，内容为 `{ id }`
+pub const TOPIC_PLUGIN_UNLOADED: &str = "plugin.unloaded";
+/// 插件已启用，内容为 `{ id }`
+pub const TOPIC_PLUGIN_ENABLED: &str = "plugin.enabled";
+/// 插件已禁用，内容为 `{ id }`
+pub const TOPIC_PLUGIN_DISABLED: &str = "plugin.disabled";
+/// 插件包已安装，内容为 `{ id, name, version }`
+pub const TOPIC_PLUGIN_INSTALLED: &str = "plugin.installed";
+/// 插件包已卸载，内容为 `{ id }`
+pub const TOPIC_PLUGIN_UNINSTALLED: &str = "plugin.uninstalled";
+/// 启动时已按注册表恢复插件，内容为恢复结果 `{ loaded, added, changed, missing, disabled, failed }`
+pub const TOPIC_PLUGINS_RESTORED: &str = "plugin.restored";
+/// 配置已修改，内容为 `{ key, value }`
+pub const TOPIC_CONFIG_CHANGED: &str = "config.changed";
+/// 窗口事件 `window.created`、`window.focused`、`window.blurred`、`window.minimized`、`window.closed`，
+/// 内容为 `{ id }`
+pub const TOPIC_WINDOW_PREFIX: &str = "window.";
+
+/// 只能由宿主发布的主题前缀
+pub const HOST_TOPIC_PREFIXES: &[&str] = &["plugin.", "config.", TOPIC_WINDOW_PREFIX];
+
+define_host_group! {
+    HostEvents,
+    /// 订阅主题，以`.*`结尾时匹配该前缀下的所有主题（如 `plugin.*`），`*` 匹配所有主题；
+    /// 事件通过 `Plugin::on_event` 送达，插件卸载时自动取消订阅
+    (subscribe, String, ()),
+    /// 取消订阅，参数与订阅时相同
+    (unsubscribe, String, ()),
+    /// 发布事件，参数为 (主题, 内容)；宿主主题（见 [HOST_TOPIC_PREFIXES]）只能由宿主发布
+    (publish, (String, Value), ()),
+}
+
+/// 主题是否匹配订阅
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some("") => true,
+        Some(prefix) if prefix.ends_with('.') => topic.starts_with(prefix),
+        _ => pattern == topic,
+    }
+}
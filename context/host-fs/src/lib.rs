@@ -0,0 +1,136 @@
+use context::define_host_group;
+use plugin::Binary;
+use serde::{Deserialize, Serialize};
+
+/// 文件或文件夹信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsEntry {
+    pub path: String,
+    pub dir: bool,
+    pub size: u64,
+    /// 修改时间，unix 时间戳（毫秒）
+    pub modified: u64,
+}
+
+/// 读取文件的一段，`len` 为空时读取到文件末尾
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadRequest {
+    pub path: String,
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default)]
+    pub len: Option<u64>,
+}
+
+/// 写入文件，父文件夹不存在时创建
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteRequest {
+    pub path: String,
+    pub data: Binary,
+    /// 追加到文件末尾，否则覆盖
+    #[serde(default)]
+    pub append: bool,
+}
+
+/// 被监听文件夹中的变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsChange {
+    pub kind: FsChangeKind,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsChangeKind {
+    Create,
+    Modify,
+    Remove,
+    Other,
+}
+
+// 路径为相对路径时相对于插件的数据文件夹（见 `data_dir`）；
+// 绝对路径须位于插件的数据文件夹、缓存文件夹或用户授权的路径中，按解析符号链接后的真实路径判断
+define_host_group! {
+    HostFs,
+    /// 读取文件，单次最多读取 4MB，大文件使用 [FsStream] 分段读取
+    (fs_read, ReadRequest, Binary),
+    /// 写入文件
+    (fs_write, WriteRequest, ()),
+    /// 列出文件夹内容
+    (fs_list, String, Vec<FsEntry>),
+    /// 文件或文件夹信息
+    (fs_stat, String, FsEntry),
+    /// 创建文件夹（包括父文件夹）
+    (fs_mkdir, String, ()),
+    /// 删除文件或文件夹
+    (fs_remove, String, ()),
+    /// 监听文件夹（含子文件夹）的变化，返回监听 id，通过 `fs_changes` 获取变化
+    (fs_watch, String, u64),
+    /// 取出监听到的变化
+    (fs_changes, u64, Vec<FsChange>),
+    /// 停止监听；插件卸载时自动停止
+    (fs_unwatch, u64, ()),
+    /// 请求访问用户指定的路径（含子路径），由用户确认，返回是否已授权
+    (fs_request_access, String, bool),
+    /// 当前插件的缓存文件夹（不存在时创建），可能被用户清理
+    (cache_dir, (), String),
+}
+
+/// 分段读取大文件
+///
+/// ```ignore
+/// let mut stream = FsStream::new(ctx, "video.mp4");
+/// while let Some(chunk) = stream.next().await? {
+///     // ...
+/// }
+/// ```
+pub struct FsStream<'a> {
+    ctx: &'a dyn Context,
+    path: String,
+    offset: u64,
+    chunk: u64,
+    done: bool,
+}
+
+impl<'a> FsStream<'a> {
+    /// 默认每次读取 1MB
+    pub fn new(ctx: &'a dyn Context, path: impl Into<String>) -> Self {
+        Self {
+            ctx,
+            path: path.into(),
+            offset: 0,
+            chunk: 1024 * 1024,
+            done: false,
+        }
+    }
+
+    pub fn with_chunk(mut self, chunk: u64) -> Self {
+        self.chunk = chunk;
+        self
+    }
+
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// 下一段数据，读到文件末尾时返回 None
+    pub async fn next(&mut self) -> PluginResult<Option<Binary>> {
+        if self.done {
+            return Ok(None);
+        }
+        let request = ReadRequest {
+            path: self.path.clone(),
+            offset: self.offset,
+            len: Some(self.chunk),
+        };
+        let data = self.ctx.fs_read(request).await?;
+        // 宿主可能返回少于 chunk 的数据，读到空数据才结束
+        if data.data.is_empty() {
+            self.done = true;
+            return Ok(None);
+        }
+        self.offset += data.data.len() as u64;
+        Ok(Some(data))
+    }
+}
@@ -1,6 +1,7 @@
 use context::define_host_group;
 // host-api/src/plugin_mgmt.rs
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginInfo {
@@ -8,6 +9,113 @@ pub struct PluginInfo {
     pub version: String,
     pub libfile: String,
     pub uiurl: String,
+    #[serde(default)]
+    pub spa: bool,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub exports: Vec<String>,
+    #[serde(default)]
+    pub serial: Vec<String>,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub publisher: Option<String>,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+/// 扫描到的描述文件的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanOutcome {
+    /// 已加载
+    Loaded,
+    /// 插件已加载或已禁用，未重新加载
+    Skipped,
+    /// 不是合法的插件描述文件
+    InvalidJson,
+    /// 描述文件中的库文件不存在
+    MissingLibfile,
+    /// 插件库的接口版本与宿主不一致，需用当前版本重新编译
+    AbiMismatch,
+    /// 与本次扫描中先出现的插件 id 相同
+    DuplicateId,
+    /// 其他加载失败，如签名、依赖或宿主 API 版本不符
+    Failed,
+}
+
+/// 单个描述文件的扫描结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanEntry {
+    /// 描述文件路径
+    pub path: String,
+    /// 描述文件无法解析时为空
+    pub id: Option<Pid>,
+    pub name: Option<String>,
+    pub outcome: ScanOutcome,
+    pub error: Option<String>,
+    /// 处理该描述文件的耗时（毫秒）
+    pub millis: u64,
+}
+
+/// 扫描结果，包含扫描到的每个描述文件，按处理顺序排列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub dir: String,
+    pub entries: Vec<ScanEntry>,
+    /// 总耗时（毫秒）
+    pub millis: u64,
+}
+
+impl ScanReport {
+    /// 已加载的插件
+    pub fn loaded(&self) -> Vec<Pid> {
+        self.entries
+            .iter()
+            .filter(|e| e.outcome == ScanOutcome::Loaded)
+            .filter_map(|e| e.id.clone())
+            .collect()
+    }
+}
+
+/// 插件目录的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginRoot {
+    /// 项目配置的插件目录
+    Project,
+    /// 用户插件目录
+    User,
+    /// 系统插件目录
+    System,
+}
+
+/// 发现的插件的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoveryStatus {
+    Loaded,
+    Disabled,
+    /// 可以加载但尚未加载
+    Available,
+    /// 描述文件无法解析
+    Invalid,
+}
+
+/// 插件目录中发现的插件描述文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredPlugin {
+    pub path: String,
+    pub root: PluginRoot,
+    pub status: DiscoveryStatus,
+    /// 描述文件无法解析时为空
+    pub id: Option<Pid>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub error: Option<String>,
 }
 
 type Pid = String;
@@ -16,12 +124,26 @@ define_host_group! {
     HostPM,
     /// 加载插件
     (load_plugin, PluginInfo, Pid),
-    /// 卸载插件
+    /// 卸载插件；有已加载的插件依赖它时失败
     (unload_plugin, Pid, ()),
+    /// 强制卸载插件，先卸载并禁用所有（直接或间接）依赖它的插件，返回卸载的插件
+    (force_unload_plugin, Pid, Vec<Pid>),
     /// 重新加载插件
     (reload_plugin, (Pid, PluginInfo), ()),
     /// 获取所有已加载的插件
     (list_plugins, (), Vec<(Pid, PluginInfo)>),
-    /// 扫描指定文件夹，根据其中2级文件夹内的*.json解析成PluginInfo格式
-    (scan, String, Vec<Pid>),
+    /// 启用插件：加载已登记但被禁用的插件，启用状态重启后保留
+    (enable_plugin, Pid, ()),
+    /// 禁用插件：卸载插件但保留登记信息，重启后不再自动加载；有已加载的插件依赖它时失败
+    (disable_plugin, Pid, ()),
+    /// 安装插件包（zip 或 tar(.gz)）到插件安装目录，已安装时升级，失败时回滚
+    (install_plugin, String, Pid),
+    /// 卸载并删除通过插件包安装的插件
+    (uninstall_plugin, Pid, ()),
+    /// 扫描指定文件夹，查找其中的插件描述文件并按依赖顺序加载，返回每个描述文件的处理结果
+    (scan, String, ScanReport),
+    /// 查找项目、用户和系统插件目录中的插件，不加载
+    (discover, (), Vec<DiscoveredPlugin>),
+    /// 登记并加载插件目录中发现的插件（参数为描述文件路径），返回插件 id；之后随注册表恢复
+    (register_plugin, String, String),
 }
@@ -0,0 +1,26 @@
+use context::define_host_group;
+use serde::{Deserialize, Serialize};
+
+/// 批量操作中的一项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum StorageOp {
+    Set { key: String, value: Value },
+    Delete { key: String },
+}
+
+define_host_group! {
+    HostStorage,
+    /// 读取当前插件存储的值，不存在时返回 None；每个插件只能访问自己的数据
+    (storage_get, String, Option<Value>),
+    /// 写入当前插件的值，超出配额时返回错误
+    (storage_set, (String, Value), ()),
+    /// 删除当前插件的值，返回是否存在
+    (storage_delete, String, bool),
+    /// 列出当前插件以指定前缀开头的键，前缀为空时返回全部
+    (storage_keys, String, Vec<String>),
+    /// 按顺序原子地执行一组写入/删除，任一项失败或超出配额时全部不生效
+    (storage_batch, Vec<StorageOp>, ()),
+    /// 当前插件的数据文件夹（不存在时创建），用于存放键值之外的文件
+    (data_dir, (), String),
+}
@@ -1,8 +1,8 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{
-    FnArg, ImplItem, ImplItemFn, ItemImpl, Pat, PatIdent, PatType, ReturnType, Signature, Type,
-    TypeParamBound, TypeReference, parse_macro_input,
+    FnArg, GenericArgument, ImplItem, ImplItemFn, ItemImpl, Pat, PatIdent, PatType, PathArguments,
+    PathSegment, ReturnType, Signature, Type, TypeParamBound, TypeReference, parse_macro_input,
 };
 
 /// 所有以该字符串开头的方法被视为可被调用的插件方法
@@ -13,6 +13,8 @@ const NAME_METHOD: &str = "method";
 const NAME_PARAMS: &str = "params";
 
 const NO_INPUT_PARAM: &str = "Context";
+/// 返回 `Vec<u8>` 或 `Bytes` 的方法的二进制数据类型
+const BINARY_MIME: &str = "application/octet-stream";
 
 /// 属性宏，用于修饰一个 impl 块，生成对应的 `Plugin` trait 实现
 ///
@@ -31,6 +33,7 @@ const NO_INPUT_PARAM: &str = "Context";
 /// // 展开后自动实现 Plugin::call，支持：
 /// // - 零参数：params 可为任意值（将被忽略）
 /// // - 多参数：params 应为对象，字段名与参数名一致
+/// // - 返回 Vec<u8> 或 Bytes（包括在 Result 中）时包装为 `::plugin::Binary`，宿主通过二进制通道发送给前端
 /// // 展开
 /// #[async_trait]
 /// impl ::plugin::Plugin for MyPlugin {
@@ -69,11 +72,16 @@ pub fn call(_args: TokenStream, input: TokenStream) -> TokenStream {
             Box::new(#ident::default())
         }
 
+        #[unsafe(no_mangle)]
+        pub fn plugin_abi() -> u32 {
+            ::plugin::ABI_VERSION
+        }
+
         #[::plugin::async_trait]
         impl #generics ::plugin::Plugin for #ident #where_clause {
             async fn call(&self, input: ::plugin::Value, ctx: &dyn ::plugin::Context) -> Result<::plugin::Value, Box<dyn std::error::Error + Send + Sync>> {
                 let err = |msg: String| -> Box<dyn std::error::Error + Send + Sync> {
-                    Box::new(std::io::Error::new(std::io::ErrorKind::Other, msg))
+                    Box::new(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg))
                 };
                 let (method, params) = match input {
                     ::plugin::Value::Object(mut map) => {
@@ -176,6 +184,19 @@ fn generate_match_arms(
             }
         }
 
+        // 若原方法返回 Result，则保留错误链
+        let try_ts = if is_result(&method.sig) {
+            quote! { ? }
+        } else {
+            quote! {}
+        };
+        // 返回 Vec<u8> 或 Bytes 时包装为二进制数据，宿主不经 JSON 编码发送给前端
+        let to_value = if returns_bytes(&method.sig) {
+            quote! { ::plugin::to_value(::plugin::Binary::new(#BINARY_MIME, result))? }
+        } else {
+            quote! { ::plugin::to_value(result)? }
+        };
+
         // 构建调用表达式
         let call_expr = if had_context {
             if params.is_empty() {
@@ -196,8 +217,8 @@ fn generate_match_arms(
         // 处理参数反序列化（多参数时生成 Args 结构体）
         let stmts = if params.is_empty() {
             quote! {{
-                let result = #call_expr;
-                Ok(::plugin::to_value(result)?)
+                let result = #call_expr #try_ts;
+                Ok(#to_value)
             }}
         } else {
             let field_names: Vec<_> = params.iter().map(|(name, _)| name).collect();
@@ -206,22 +227,13 @@ fn generate_match_arms(
             quote! {{
                 #[derive(::serde::Deserialize)]
                 struct Args { #( #field_names: #field_types, )* }
-                let Args { #(#call_args,)* } = ::plugin::from_value(params)?;
-                let result = #call_expr;
-                Ok(::plugin::to_value(result)?)
+                let Args { #(#call_args,)* } = ::plugin::from_value(params).map_err(|e| err(e.to_string()))?;
+                let result = #call_expr #try_ts;
+                Ok(#to_value)
             }}
         };
 
-        // 若原方法返回 Result，则保留错误链
-        let final_code = if is_result(&method.sig) {
-            quote! {{
-                (async { #stmts }).await?
-            }}
-        } else {
-            stmts
-        };
-
-        arms.push(quote! { #method_name_str => #final_code });
+        arms.push(quote! { #method_name_str => #stmts });
     }
     Ok(arms)
 }
@@ -247,6 +259,43 @@ fn extract_param_name(pat: &Pat) -> Result<proc_macro2::Ident, syn::Error> {
     }
 }
 
+/// 判断函数返回值（为 Result 时取其 Ok 类型）是否为 `Vec<u8>` 或 `Bytes`
+fn returns_bytes(sig: &Signature) -> bool {
+    let ReturnType::Type(_, ty) = &sig.output else {
+        return false;
+    };
+    let mut segment = last_segment(ty);
+    if let Some(s) = segment
+        && s.ident == "Result"
+    {
+        segment = first_generic(s).and_then(last_segment);
+    }
+    segment.is_some_and(|s| {
+        s.ident == "Bytes"
+            || (s.ident == "Vec"
+                && first_generic(s)
+                    .and_then(last_segment)
+                    .is_some_and(|s| s.ident == "u8"))
+    })
+}
+
+fn first_generic(segment: &PathSegment) -> Option<&Type> {
+    if let PathArguments::AngleBracketed(args) = &segment.arguments
+        && let Some(GenericArgument::Type(ty)) = args.args.first()
+    {
+        return Some(ty);
+    }
+    None
+}
+
+/// 类型路径的最后一段
+fn last_segment(ty: &Type) -> Option<&PathSegment> {
+    match ty {
+        Type::Path(path) => path.path.segments.last(),
+        _ => None,
+    }
+}
+
 /// 判断函数返回类型是否为 Result
 fn is_result(sig: &Signature) -> bool {
     if let ReturnType::Type(_, ty) = &sig.output
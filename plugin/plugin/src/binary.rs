@@ -0,0 +1,53 @@
+use crate::Value;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error, ser::SerializeMap};
+
+/// 二进制数据在 JSON 中的标记字段，值为 base64 编码的内容
+pub const BINARY_KEY: &str = "$binary";
+/// 二进制数据的 MIME 类型字段
+pub const BINARY_MIME_KEY: &str = "mime";
+
+/// 插件返回的二进制数据
+///
+/// 序列化为 `{ "$binary": "<base64>", "mime": "image/png" }`，宿主识别后按原始字节返回
+#[derive(Debug, Clone)]
+pub struct Binary {
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+impl Binary {
+    pub fn new(mime: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            mime: mime.into(),
+            data: data.into(),
+        }
+    }
+
+    /// 从插件返回值中识别二进制数据，不是二进制格式时返回 None
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let map = value.as_object()?;
+        let data = STANDARD.decode(map.get(BINARY_KEY)?.as_str()?).ok()?;
+        let mime = map
+            .get(BINARY_MIME_KEY)
+            .and_then(Value::as_str)
+            .unwrap_or("application/octet-stream");
+        Some(Self::new(mime, data))
+    }
+}
+
+impl Serialize for Binary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry(BINARY_KEY, &STANDARD.encode(&self.data))?;
+        map.serialize_entry(BINARY_MIME_KEY, &self.mime)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Binary {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Self::from_value(&value).ok_or_else(|| D::Error::custom("invalid binary data"))
+    }
+}
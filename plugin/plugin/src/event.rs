@@ -0,0 +1,13 @@
+use crate::Value;
+use serde::{Deserialize, Serialize};
+
+/// 事件总线上的事件，插件通过 [Plugin::on_event](crate::Plugin::on_event) 接收订阅的事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    /// 以`.`分隔的主题，如 `plugin.loaded`
+    pub topic: String,
+    /// 发布方：`host`、`ui` 或发布事件的插件名
+    pub source: String,
+    #[serde(default)]
+    pub payload: Value,
+}
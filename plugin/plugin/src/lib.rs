@@ -1,16 +1,38 @@
+mod binary;
+mod event;
+mod log;
+
 pub use async_trait::async_trait;
+pub use binary::*;
+pub use event::*;
+pub use log::*;
 pub use plugin_macro::call;
 pub use serde_json::{Value, from_value, to_value};
 pub type PluginResult<T, E = Box<dyn std::error::Error + Send + Sync>> = Result<T, E>;
 
+/// 插件接口版本，由 [call] 导出为 `plugin_abi` 函数，宿主加载时检查是否一致；
+/// 不使用 [call] 时需自行导出。[Plugin] 或 [Context] 的定义变化时递增。
+///
+/// - 1：首个带 `plugin_abi` 的版本，此前构建的插件没有该函数，加载时被拒绝
+/// - 2：[Context] 增加 `log_record`、[Plugin] 增加 `on_event` 后的虚表；版本检查晚于这两处改动引入，
+///   递增版本以明确区分改动前后构建的插件
+pub const ABI_VERSION: u32 = 2;
+
 pub mod prelude {
-    pub use crate::{Plugin, PluginResult, Value, async_trait, call, from_value, to_value};
+    pub use crate::{
+        Binary, ContextLogExt, Event, LogLevel, LogRecord, Plugin, PluginResult, Value,
+        async_trait, call, from_value, to_value,
+    };
 }
 
 #[async_trait]
 pub trait Context: Send + Sync {
     /// 插件将日志输出到主应用(可以利用主应用的日志文件写入)
-    fn log(&self, _msg: &str) {}
+    fn log(&self, msg: &str) {
+        self.log_record(LogRecord::new(LogLevel::Debug, msg));
+    }
+    /// 带级别、字段和 span 的日志，见 [ContextLogExt]
+    fn log_record(&self, _record: LogRecord) {}
     // 让host可以自行拓展而需要更改此处定义
     async fn call_host(&self, cmd: &str, _args: Value) -> PluginResult<Value> {
         Err(format!("Host command '{}' not supported now", cmd).into())
@@ -20,6 +42,10 @@ pub trait Context: Send + Sync {
 #[async_trait]
 pub trait Plugin {
     async fn call(&self, input: Value, ctx: &dyn Context) -> PluginResult<Value>;
+    /// 接收订阅的事件（见宿主方法 `subscribe`），默认忽略
+    async fn on_event(&self, _event: Event, _ctx: &dyn Context) -> PluginResult<()> {
+        Ok(())
+    }
 }
 
 impl Context for () {}
@@ -0,0 +1,159 @@
+use crate::{Context, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+
+/// 日志级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// 插件通过 [Context::log_record] 发给宿主的一条日志
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub message: String,
+    /// 结构化字段
+    #[serde(default)]
+    pub fields: Map<String, Value>,
+    /// 所在的 span，由外到内
+    #[serde(default)]
+    pub spans: Vec<String>,
+}
+
+impl LogRecord {
+    pub fn new(level: LogLevel, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+            fields: Map::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.fields.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// 一组带有相同字段的日志，如一次下载、一次请求
+///
+/// ```ignore
+/// let span = ctx.span("download").field("url", url);
+/// span.info("start");
+/// span.child("retry").warn("timeout");
+/// ```
+pub struct Span<'a> {
+    ctx: &'a dyn Context,
+    names: Vec<String>,
+    fields: Map<String, Value>,
+}
+
+impl<'a> Span<'a> {
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.fields.insert(key.into(), value.into());
+        self
+    }
+
+    /// 嵌套的 span，继承字段
+    pub fn child(&self, name: impl Into<String>) -> Span<'a> {
+        let mut names = self.names.clone();
+        names.push(name.into());
+        Span {
+            ctx: self.ctx,
+            names,
+            fields: self.fields.clone(),
+        }
+    }
+
+    /// 以该 span 的字段和名称补全日志后发送
+    pub fn record(&self, mut record: LogRecord) {
+        for (k, v) in &self.fields {
+            record.fields.entry(k.clone()).or_insert_with(|| v.clone());
+        }
+        let mut spans = self.names.clone();
+        spans.append(&mut record.spans);
+        record.spans = spans;
+        self.ctx.log_record(record);
+    }
+
+    pub fn log(&self, level: LogLevel, msg: impl Into<String>) {
+        self.record(LogRecord::new(level, msg));
+    }
+
+    pub fn trace(&self, msg: impl Into<String>) {
+        self.log(LogLevel::Trace, msg)
+    }
+
+    pub fn debug(&self, msg: impl Into<String>) {
+        self.log(LogLevel::Debug, msg)
+    }
+
+    pub fn info(&self, msg: impl Into<String>) {
+        self.log(LogLevel::Info, msg)
+    }
+
+    pub fn warn(&self, msg: impl Into<String>) {
+        self.log(LogLevel::Warn, msg)
+    }
+
+    pub fn error(&self, msg: impl Into<String>) {
+        self.log(LogLevel::Error, msg)
+    }
+}
+
+/// 插件侧的日志便捷方法
+pub trait ContextLogExt {
+    /// 顶层 span，等价于不带名称的日志入口
+    fn logger(&self) -> Span<'_>;
+
+    fn span(&self, name: impl Into<String>) -> Span<'_> {
+        self.logger().child(name)
+    }
+
+    fn trace(&self, msg: impl Into<String>) {
+        self.logger().trace(msg)
+    }
+
+    fn debug(&self, msg: impl Into<String>) {
+        self.logger().debug(msg)
+    }
+
+    fn info(&self, msg: impl Into<String>) {
+        self.logger().info(msg)
+    }
+
+    fn warn(&self, msg: impl Into<String>) {
+        self.logger().warn(msg)
+    }
+
+    fn error(&self, msg: impl Into<String>) {
+        self.logger().error(msg)
+    }
+}
+
+impl<T: Context> ContextLogExt for T {
+    fn logger(&self) -> Span<'_> {
+        Span {
+            ctx: self,
+            names: Vec::new(),
+            fields: Map::new(),
+        }
+    }
+}
+
+impl ContextLogExt for dyn Context + '_ {
+    fn logger(&self) -> Span<'_> {
+        Span {
+            ctx: self,
+            names: Vec::new(),
+            fields: Map::new(),
+        }
+    }
+}
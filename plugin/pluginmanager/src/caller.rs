@@ -0,0 +1,53 @@
+use crate::PluginId;
+use plugin::{Context, LogRecord, PluginResult, Value, async_trait};
+
+tokio::task_local! {
+    static CALLER: Caller;
+}
+
+/// 正在调用宿主的插件
+///
+/// [PluginManager::call](crate::PluginManager::call) 会将宿主上下文包装为单个插件的上下文，
+/// 插件调用 `log_record`/`call_host` 期间，宿主侧可通过 [Caller::current] 读取调用方，
+/// 用于权限检查、日志归属、存储隔离和审计；在宿主方法中另起的任务里读取不到
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub plugin: PluginId,
+    pub name: String,
+    pub version: String,
+    /// 本次 [PluginManager::call](crate::PluginManager::call) 的编号，进程内唯一
+    pub call_id: u64,
+    /// 插件之间互相调用时，由外到内经过的插件，不含自身；由宿主直接调用时为空
+    pub chain: Vec<PluginId>,
+}
+
+impl Caller {
+    /// 当前调用方，不在插件调用中时返回 None
+    pub fn current() -> Option<Caller> {
+        CALLER.try_with(Clone::clone).ok()
+    }
+
+    /// 直接调用当前插件的插件，由宿主直接调用时返回 None
+    pub fn parent(&self) -> Option<&PluginId> {
+        self.chain.last()
+    }
+}
+
+/// 限定为单个插件的上下文
+pub(crate) struct ScopedContext<'a> {
+    pub(crate) host: &'a dyn Context,
+    pub(crate) caller: Caller,
+}
+
+#[async_trait]
+impl Context for ScopedContext<'_> {
+    fn log_record(&self, record: LogRecord) {
+        CALLER.sync_scope(self.caller.clone(), || self.host.log_record(record))
+    }
+
+    async fn call_host(&self, cmd: &str, args: Value) -> PluginResult<Value> {
+        CALLER
+            .scope(self.caller.clone(), self.host.call_host(cmd, args))
+            .await
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{PluginError, PluginInfo};
+pub use semver::{Version, VersionReq};
+
+/// 解析版本号，允许省略次版本号和修订号（如 `2`、`2.0`）及前缀 `v`
+pub fn parse_version(version: &str) -> Result<Version, PluginError> {
+    let version = version.trim().trim_start_matches('v');
+    Version::parse(version)
+        .or_else(|e| {
+            let (core, rest) = version
+                .find(['-', '+'])
+                .map_or((version, ""), |i| version.split_at(i));
+            match core.matches('.').count() {
+                0 => Version::parse(&format!("{core}.0.0{rest}")),
+                1 => Version::parse(&format!("{core}.0{rest}")),
+                _ => Err(e),
+            }
+        })
+        .map_err(|e| PluginError::InvalidVersion(format!("{version}: {e}")))
+}
+
+/// 解析版本要求，如 `^1.2`、`>=1.0, <2`
+pub fn parse_requirement(req: &str) -> Result<VersionReq, PluginError> {
+    VersionReq::parse(req).map_err(|e| PluginError::InvalidVersion(format!("{req}: {e}")))
+}
+
+/// 按依赖关系排序：被依赖的插件排在前面，其余保持原有顺序；
+/// 依赖不在列表中的插件（已加载或缺失）不影响排序
+///
+/// 返回排序后的下标，以及因循环依赖无法排序的插件下标
+pub fn load_order(infos: &[PluginInfo]) -> (Vec<usize>, Vec<usize>) {
+    let mut placed = vec![false; infos.len()];
+    let mut order = Vec::with_capacity(infos.len());
+    let ready = |i: usize, placed: &[bool]| {
+        infos[i].dependencies.keys().all(|dep| {
+            let providers: Vec<usize> = (0..infos.len())
+                .filter(|&j| infos[j].name == *dep)
+                .collect();
+            providers.is_empty() || providers.iter().any(|&j| placed[j])
+        })
+    };
+    while let Some(i) = (0..infos.len()).find(|&i| !placed[i] && ready(i, &placed)) {
+        placed[i] = true;
+        order.push(i);
+    }
+    let cyclic = (0..infos.len()).filter(|&i| !placed[i]).collect();
+    (order, cyclic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn info(name: &str, deps: &[&str]) -> PluginInfo {
+        let dependencies: BTreeMap<&str, &str> = deps.iter().map(|d| (*d, "*")).collect();
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "version": "1.0.0",
+            "libfile": "",
+            "uiurl": "",
+            "dependencies": dependencies,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn short_versions() {
+        assert_eq!(parse_version("v2").unwrap(), Version::new(2, 0, 0));
+        assert_eq!(parse_version("1.2-beta").unwrap().to_string(), "1.2.0-beta");
+        assert!(parse_version("x").is_err());
+    }
+
+    #[test]
+    fn dependencies_first() {
+        let infos = [
+            info("app", &["ui", "core"]),
+            info("ui", &["core"]),
+            info("core", &[]),
+        ];
+        let (order, cyclic) = load_order(&infos);
+        assert_eq!(order, [2, 1, 0]);
+        assert!(cyclic.is_empty());
+    }
+
+    #[test]
+    fn missing_dependency_does_not_block() {
+        let infos = [info("a", &["loaded-elsewhere"]), info("b", &[])];
+        assert_eq!(load_order(&infos), (vec![0, 1], vec![]));
+    }
+
+    #[test]
+    fn cycles_and_their_dependents() {
+        let infos = [
+            info("a", &["b"]),
+            info("b", &["a"]),
+            info("c", &["a"]),
+            info("d", &[]),
+            info("self", &["self"]),
+        ];
+        let (order, cyclic) = load_order(&infos);
+        assert_eq!(order, [3]);
+        assert_eq!(cyclic, [0, 1, 2, 4]);
+    }
+}
@@ -8,6 +8,45 @@ pub enum PluginError {
     UnExistResource(String),
     #[error("Load Plugin Error: {0}")]
     LoadErr(#[from] libloading::Error),
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
     #[error("Plugin is not found")]
     PluginNotFound,
+    #[error("Plugin is not signed")]
+    Unsigned,
+    #[error("Plugin publisher is not trusted: {0}")]
+    UntrustedPublisher(String),
+    #[error("Plugin publisher key is invalid: {0}")]
+    InvalidPublisherKey(String),
+    #[error("Plugin signature is invalid: {0}")]
+    InvalidSignature(String),
+    #[error("Plugin call cycle: {0}")]
+    CallCycle(String),
+    #[error("Plugin call is nested too deeply: {0}")]
+    CallTooDeep(String),
+    #[error("Plugin ABI version mismatch: {0}")]
+    AbiMismatch(String),
+    #[error("Invalid version: {0}")]
+    InvalidVersion(String),
+    #[error("Plugin requires host API {0}")]
+    IncompatibleHost(String),
+    #[error("Plugin dependency is not loaded: {0}")]
+    MissingDependency(String),
+    #[error("Plugin dependency version conflict: {0}")]
+    DependencyConflict(String),
+    #[error("Plugin is busy: {0}")]
+    Busy(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+}
+
+impl PluginError {
+    /// 供调用方区分处理的错误码，如调用被拒绝（`busy`）时可稍后重试
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            PluginError::Busy(_) => Some("busy"),
+            PluginError::PermissionDenied(_) => Some("permission_denied"),
+            _ => None,
+        }
+    }
 }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginInfo {
@@ -6,9 +7,38 @@ pub struct PluginInfo {
     pub version: String,
     pub libfile: String,
     pub uiurl: String,
+    /// 插件 UI 是否为单页应用：为 true 时无扩展名的页面请求回退到 index.html
+    #[serde(default)]
+    pub spa: bool,
+    /// 插件需要调用的宿主方法，以 `组名` 或 `组名.方法名` 声明，如 `HostConfig`、`HostPM.scan`
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// 允许其他插件调用的方法，`*` 表示全部
+    #[serde(default)]
+    pub exports: Vec<String>,
+    /// 不能并发执行的方法，同一方法的调用依次执行
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub serial: Vec<String>,
+    /// 依赖的其他插件：插件名到版本要求（semver，如 `^1.2`），加载前需先加载依赖
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, String>,
+    /// 要求的宿主 API 版本（semver，如 `>=1.1`），见 `context::HOST_API_VERSION`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// 发布者公钥（base64），见 [Verifier](crate::Verifier)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    /// 发布者对插件的签名（base64）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 impl PluginInfo {
+    /// 插件是否在 `exports` 或 `serial` 中列出了该方法（`*` 不算）
+    pub fn declares(&self, method: &str) -> bool {
+        self.exports.iter().chain(&self.serial).any(|m| m == method)
+    }
+
     /// 通过父文件夹将相对文件路径转换为绝对路径
     pub fn canonicalize_by_parent(&self, parent: impl AsRef<std::path::Path>) -> Self {
         let parent = parent.as_ref();
@@ -23,6 +53,14 @@ impl PluginInfo {
             version: self.version.clone(),
             libfile,
             uiurl,
+            spa: self.spa,
+            permissions: self.permissions.clone(),
+            exports: self.exports.clone(),
+            serial: self.serial.clone(),
+            dependencies: self.dependencies.clone(),
+            host: self.host.clone(),
+            publisher: self.publisher.clone(),
+            signature: self.signature.clone(),
         }
     }
 }
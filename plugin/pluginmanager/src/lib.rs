@@ -1,8 +1,18 @@
+mod caller;
+mod deps;
 mod error;
 mod info;
+mod limit;
+mod metrics;
 mod pm;
+mod verify;
 
+pub use caller::Caller;
+pub use deps::*;
 pub use error::*;
 pub use info::*;
+pub use limit::{CallLimits, Overflow};
+pub use metrics::*;
 pub use plugin;
 pub use pm::*;
+pub use verify::*;
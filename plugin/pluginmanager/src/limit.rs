@@ -0,0 +1,280 @@
+use crate::{PluginError, PluginId};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 等待队列已满时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    /// 拒绝调用，返回 [PluginError::Busy]
+    #[default]
+    Reject,
+    /// 等待队列空出，最长等待 [CallLimits::timeout]
+    Wait,
+}
+
+/// 插件调用的并发限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CallLimits {
+    /// 每个插件同时执行的调用数，0 为不限
+    pub per_plugin: usize,
+    /// 插件声明的每个方法（见 [PluginInfo::declares](crate::PluginInfo::declares)）同时执行的调用数，
+    /// 0 为不限；插件声明的串行方法始终为 1
+    pub per_method: usize,
+    /// 每个插件等待执行的调用数上限
+    pub queue: usize,
+    /// 等待队列已满时拒绝还是等待
+    pub overflow: Overflow,
+    /// 等待执行许可的最长时间（毫秒），超时返回 [PluginError::Busy]；
+    /// 插件之间互相调用时调用方占着许可等待被调用方，超时可避免互相等待而永远阻塞
+    pub timeout: u64,
+}
+
+impl Default for CallLimits {
+    fn default() -> Self {
+        Self {
+            per_plugin: 16,
+            per_method: 0,
+            queue: 64,
+            overflow: Overflow::Reject,
+            timeout: 30_000,
+        }
+    }
+}
+
+/// 按 [CallLimits] 为插件调用分配执行许可
+#[derive(Default)]
+pub(crate) struct Limiter {
+    limits: CallLimits,
+    plugins: DashMap<PluginId, Arc<Gate>>,
+    /// 只为插件声明的方法创建，调用方传入的任意方法名不会使其增长
+    methods: DashMap<(PluginId, String), Arc<Semaphore>>,
+}
+
+/// 插件的许可：`admitted` 为执行中和等待中的调用数之和，`running` 为执行中的调用数
+struct Gate {
+    admitted: Arc<Semaphore>,
+    running: Arc<Semaphore>,
+}
+
+/// 调用结束时释放
+pub(crate) struct Permit {
+    _running: Option<OwnedSemaphorePermit>,
+    _method: Option<OwnedSemaphorePermit>,
+    _admitted: Option<OwnedSemaphorePermit>,
+}
+
+impl Limiter {
+    pub(crate) fn new(limits: CallLimits) -> Self {
+        Self {
+            limits,
+            plugins: DashMap::new(),
+            methods: DashMap::new(),
+        }
+    }
+
+    /// 等待执行许可，超过 [CallLimits::timeout] 时返回 [PluginError::Busy]；
+    /// `declared` 为插件是否声明了该方法，未声明的方法不受 [CallLimits::per_method] 限制
+    pub(crate) async fn acquire(
+        &self,
+        id: &PluginId,
+        method: &str,
+        declared: bool,
+        serial: bool,
+    ) -> Result<Permit, PluginError> {
+        let timeout = Duration::from_millis(self.limits.timeout);
+        tokio::time::timeout(timeout, self.wait(id, method, declared, serial))
+            .await
+            .map_err(|_| {
+                PluginError::Busy(format!(
+                    "{method}, no permit within {}ms",
+                    self.limits.timeout
+                ))
+            })?
+    }
+
+    /// 先取方法的许可再取插件的许可，避免串行方法排队时占用插件的并发数
+    async fn wait(
+        &self,
+        id: &PluginId,
+        method: &str,
+        declared: bool,
+        serial: bool,
+    ) -> Result<Permit, PluginError> {
+        let gate = (self.limits.per_plugin > 0).then(|| {
+            self.plugins
+                .entry(id.clone())
+                .or_insert_with(|| {
+                    Arc::new(Gate {
+                        admitted: Arc::new(Semaphore::new(
+                            self.limits.per_plugin + self.limits.queue,
+                        )),
+                        running: Arc::new(Semaphore::new(self.limits.per_plugin)),
+                    })
+                })
+                .clone()
+        });
+        let admitted = match &gate {
+            Some(gate) => Some(match gate.admitted.clone().try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) if self.limits.overflow == Overflow::Wait => {
+                    closed(gate.admitted.clone().acquire_owned().await)?
+                }
+                Err(_) => {
+                    return Err(PluginError::Busy(format!(
+                        "{method}, {} calls running or queued",
+                        self.limits.per_plugin + self.limits.queue
+                    )));
+                }
+            }),
+            None => None,
+        };
+        let per_method = match (serial, declared) {
+            (true, _) => 1,
+            (false, true) => self.limits.per_method,
+            (false, false) => 0,
+        };
+        let method = match per_method {
+            0 => None,
+            n => {
+                let semaphore = self
+                    .methods
+                    .entry((id.clone(), method.to_string()))
+                    .or_insert_with(|| Arc::new(Semaphore::new(n)))
+                    .clone();
+                Some(closed(semaphore.acquire_owned().await)?)
+            }
+        };
+        let running = match gate {
+            Some(gate) => Some(closed(gate.running.clone().acquire_owned().await)?),
+            None => None,
+        };
+        Ok(Permit {
+            _running: running,
+            _method: method,
+            _admitted: admitted,
+        })
+    }
+
+    /// 插件卸载后移除其许可，等待中的调用返回 [PluginError::PluginNotFound]
+    pub(crate) fn remove(&self, id: &PluginId) {
+        if let Some((_, gate)) = self.plugins.remove(id) {
+            gate.admitted.close();
+            gate.running.close();
+        }
+        self.methods.retain(|(plugin, _), semaphore| {
+            let keep = plugin != id;
+            if !keep {
+                semaphore.close();
+            }
+            keep
+        });
+    }
+}
+
+/// 许可被关闭即插件已卸载
+fn closed<T, E>(result: Result<T, E>) -> Result<T, PluginError> {
+    result.map_err(|_| PluginError::PluginNotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_plugin: usize, queue: usize, overflow: Overflow, timeout: u64) -> Limiter {
+        Limiter::new(CallLimits {
+            per_plugin,
+            per_method: 0,
+            queue,
+            overflow,
+            timeout,
+        })
+    }
+
+    static ID: std::sync::LazyLock<PluginId> = std::sync::LazyLock::new(|| PluginId("1".into()));
+
+    async fn release_after(permit: Permit, ms: u64) {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        drop(permit);
+    }
+
+    #[tokio::test]
+    async fn reject_when_queue_is_full() {
+        let limiter = limiter(1, 0, Overflow::Reject, 1000);
+        let _running = limiter.acquire(&ID, "a", true, false).await.unwrap();
+        let result = limiter.acquire(&ID, "a", true, false).await;
+        assert!(matches!(result, Err(PluginError::Busy(_))));
+    }
+
+    #[tokio::test]
+    async fn queued_call_waits_for_permit() {
+        let limiter = limiter(1, 1, Overflow::Reject, 1000);
+        let running = limiter.acquire(&ID, "a", true, false).await.unwrap();
+        let (queued, _) = tokio::join!(
+            limiter.acquire(&ID, "a", true, false),
+            release_after(running, 50)
+        );
+        assert!(queued.is_ok());
+    }
+
+    #[tokio::test]
+    async fn wait_when_queue_is_full() {
+        let limiter = limiter(1, 0, Overflow::Wait, 1000);
+        let running = limiter.acquire(&ID, "a", true, false).await.unwrap();
+        let (waiting, _) = tokio::join!(
+            limiter.acquire(&ID, "a", true, false),
+            release_after(running, 50)
+        );
+        assert!(waiting.is_ok());
+    }
+
+    #[tokio::test]
+    async fn wait_is_bounded_by_timeout() {
+        let limiter = limiter(1, 0, Overflow::Wait, 50);
+        let _running = limiter.acquire(&ID, "a", true, false).await.unwrap();
+        match limiter.acquire(&ID, "a", true, false).await {
+            Err(PluginError::Busy(msg)) => assert!(msg.contains("no permit within 50ms")),
+            _ => panic!("expected busy"),
+        }
+    }
+
+    #[tokio::test]
+    async fn serial_method_runs_one_at_a_time() {
+        let limiter = limiter(0, 0, Overflow::Reject, 50);
+        let _running = limiter.acquire(&ID, "save", true, true).await.unwrap();
+        let same = limiter.acquire(&ID, "save", true, true).await;
+        assert!(matches!(same, Err(PluginError::Busy(_))));
+        let other = limiter.acquire(&ID, "load", true, false).await;
+        assert!(other.is_ok());
+    }
+
+    #[tokio::test]
+    async fn per_method_only_for_declared_methods() {
+        let limiter = Limiter::new(CallLimits {
+            per_method: 1,
+            timeout: 50,
+            ..Default::default()
+        });
+        let _a = limiter.acquire(&ID, "random", false, false).await.unwrap();
+        let _b = limiter.acquire(&ID, "random", false, false).await.unwrap();
+        assert!(limiter.methods.is_empty());
+        let _c = limiter.acquire(&ID, "echo", true, false).await.unwrap();
+        let d = limiter.acquire(&ID, "echo", true, false).await;
+        assert!(matches!(d, Err(PluginError::Busy(_))));
+        assert_eq!(limiter.methods.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn remove_wakes_waiting_calls() {
+        let limiter = limiter(1, 0, Overflow::Wait, 1000);
+        let _running = limiter.acquire(&ID, "a", true, false).await.unwrap();
+        let (waiting, _) = tokio::join!(limiter.acquire(&ID, "a", true, false), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            limiter.remove(&ID);
+        });
+        assert!(matches!(waiting, Err(PluginError::PluginNotFound)));
+    }
+}
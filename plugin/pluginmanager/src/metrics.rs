@@ -0,0 +1,139 @@
+use dashmap::DashMap;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// 耗时直方图各区间的上限（秒）
+pub const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// 插件未声明的方法（见 [PluginInfo::declares](crate::PluginInfo::declares)）统计时归入的方法名，
+/// 避免调用方传入的任意方法名使统计无限增长
+pub const OTHER_METHOD: &str = "other";
+
+/// 按调用目标（插件 id 或 bridge）和方法统计调用次数、失败次数、进行中的调用数及耗时分布
+#[derive(Default)]
+pub struct CallMetrics {
+    stats: DashMap<(String, String), Stats>,
+}
+
+#[derive(Default)]
+struct Stats {
+    calls: u64,
+    errors: u64,
+    in_flight: u64,
+    total: Duration,
+    /// 落在各区间内的调用数（非累计），最后一项为超出所有上限的调用
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+}
+
+/// 某个目标的某个方法的统计
+#[derive(Debug, Clone, Serialize)]
+pub struct CallStats {
+    pub target: String,
+    pub method: String,
+    /// 已完成的调用数
+    pub calls: u64,
+    /// 失败（含被取消）的调用数
+    pub errors: u64,
+    pub in_flight: u64,
+    /// 已完成调用的总耗时（秒）
+    pub seconds: f64,
+    /// 耗时不超过 [LATENCY_BUCKETS] 中对应上限的调用数（累计）
+    pub buckets: Vec<u64>,
+}
+
+/// 进行中的调用，结束时调用 [CallTimer::finish]；未调用即释放（如超时被取消）时记为失败
+pub struct CallTimer<'a> {
+    metrics: &'a CallMetrics,
+    key: Option<(String, String)>,
+    start: Instant,
+}
+
+impl CallMetrics {
+    /// 记录调用开始，返回的计时器在调用结束时记录耗时
+    pub fn start(&self, target: &str, method: &str) -> CallTimer<'_> {
+        self.begin(target, method);
+        CallTimer {
+            metrics: self,
+            key: Some((target.to_string(), method.to_string())),
+            start: Instant::now(),
+        }
+    }
+
+    /// 记录调用开始，需与 [CallMetrics::end] 成对调用
+    pub fn begin(&self, target: &str, method: &str) {
+        let key = (target.to_string(), method.to_string());
+        self.stats.entry(key).or_default().in_flight += 1;
+    }
+
+    /// 记录调用结束
+    pub fn end(&self, target: &str, method: &str, elapsed: Duration, ok: bool) {
+        let key = (target.to_string(), method.to_string());
+        let mut stats = self.stats.entry(key).or_default();
+        stats.in_flight = stats.in_flight.saturating_sub(1);
+        stats.calls += 1;
+        if !ok {
+            stats.errors += 1;
+        }
+        stats.total += elapsed;
+        let seconds = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|le| seconds <= *le)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        stats.buckets[bucket] += 1;
+    }
+
+    /// 当前的统计，按目标和方法排序
+    pub fn snapshot(&self) -> Vec<CallStats> {
+        let mut result: Vec<CallStats> = self
+            .stats
+            .iter()
+            .map(|e| {
+                let ((target, method), stats) = (e.key(), e.value());
+                let buckets = stats.buckets[..LATENCY_BUCKETS.len()]
+                    .iter()
+                    .scan(0, |sum, n| {
+                        *sum += n;
+                        Some(*sum)
+                    })
+                    .collect();
+                CallStats {
+                    target: target.clone(),
+                    method: method.clone(),
+                    calls: stats.calls,
+                    errors: stats.errors,
+                    in_flight: stats.in_flight,
+                    seconds: stats.total.as_secs_f64(),
+                    buckets,
+                }
+            })
+            .collect();
+        result.sort_by(|a, b| (&a.target, &a.method).cmp(&(&b.target, &b.method)));
+        result
+    }
+
+    /// 移除目标的统计，如插件卸载后
+    pub fn remove(&self, target: &str) {
+        self.stats.retain(|(t, _), _| t != target);
+    }
+}
+
+impl CallTimer<'_> {
+    /// 记录调用结束
+    pub fn finish(mut self, ok: bool) {
+        if let Some((target, method)) = self.key.take() {
+            self.metrics.end(&target, &method, self.start.elapsed(), ok);
+        }
+    }
+}
+
+impl Drop for CallTimer<'_> {
+    fn drop(&mut self) {
+        if let Some((target, method)) = self.key.take() {
+            self.metrics
+                .end(&target, &method, self.start.elapsed(), false);
+        }
+    }
+}
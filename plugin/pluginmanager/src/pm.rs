@@ -1,15 +1,33 @@
-use crate::{PluginError, PluginInfo};
+use crate::{
+    CallLimits, CallMetrics, Caller, OTHER_METHOD, PluginError, PluginInfo, SignaturePolicy,
+    Verifier, Version, caller::ScopedContext, limit::Limiter, parse_requirement, parse_version,
+};
 use dashmap::DashMap;
-use libcommon::{New, hash};
-use plugin::{Context, PluginResult};
-use std::sync::Arc;
+use libcommon::{New, hash, trace};
+use plugin::{Context, Event, PluginResult};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
 
 const NAME_PLUGIN_FN: &str = "plugin";
+const NAME_ABI_FN: &str = "plugin_abi";
+/// 插件之间互相调用的最大深度
+const MAX_CALL_DEPTH: usize = 8;
 type PluginFn<'a> = libloading::Symbol<'a, unsafe fn() -> Box<dyn plugin::Plugin + Send + Sync>>;
 
 #[derive(Default)]
 pub struct PluginManager {
     plugins: DashMap<PluginId, Plugin>,
+    verifier: Verifier,
+    /// 宿主 API 版本；未设置时不检查插件声明的 `host`
+    host_api: Option<Version>,
+    next_call: AtomicU64,
+    metrics: CallMetrics,
+    limiter: Limiter,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -27,15 +45,58 @@ struct Plugin {
     load: LoadPlugin,
 }
 
+/// 字段按声明顺序释放：插件对象必须先于动态库释放，动态库关闭后再删除其私有副本。
+/// 调用期间持有其副本，插件在调用中被卸载时动态库等调用结束后才关闭
+#[derive(Clone)]
 struct LoadPlugin {
-    _lib: Arc<libloading::Library>,
     plugin: Arc<Box<dyn plugin::Plugin + Send + Sync>>,
+    _lib: Arc<libloading::Library>,
+    _copy: Option<Arc<PrivateCopy>>,
+}
+
+/// 库文件所在文件夹的副本，位于仅当前用户可访问的临时文件夹中，释放时删除。
+/// 库文件写入校验过的内容，同一文件夹中的其他文件（如依赖的动态库）原样复制，
+/// 使按 `$ORIGIN` 或 DLL 所在文件夹查找的依赖仍能找到
+struct PrivateCopy {
+    dir: PathBuf,
 }
 
 impl PluginManager {
+    /// 设置加载插件前的签名校验
+    pub fn with_verifier(mut self, verifier: Verifier) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    /// 设置宿主 API 版本，加载插件时检查其 `host` 要求
+    pub fn with_host_api(mut self, version: Version) -> Self {
+        self.host_api = Some(version);
+        self
+    }
+
+    /// 设置插件调用的并发限制
+    pub fn with_limits(mut self, limits: CallLimits) -> Self {
+        self.limiter = Limiter::new(limits);
+        self
+    }
+
     pub fn load(&self, info: impl Into<PluginInfo>) -> Result<PluginId, PluginError> {
         let info = info.into();
-        let load = LoadPlugin::load(&info.libfile)?;
+        // 库文件只读取一次：校验读到的内容并从其私有副本加载，避免校验后文件被替换
+        let lib = match self.verifier.policy() {
+            SignaturePolicy::Off => None,
+            _ => {
+                let lib = std::fs::read(&info.libfile)
+                    .map_err(|_| PluginError::UnExistResource(info.libfile.clone()))?;
+                self.verifier.check(&info, &lib)?;
+                Some(lib)
+            }
+        };
+        self.check_requirements(&info)?;
+        let load = match lib {
+            Some(lib) => LoadPlugin::load_verified(&info.libfile, &lib)?,
+            None => LoadPlugin::load(&info.libfile)?,
+        };
         let id = PluginId::from(&info);
 
         let p = Plugin::new(info, load);
@@ -45,6 +106,83 @@ impl PluginManager {
 
     pub fn unload(&self, id: &PluginId) {
         self.plugins.remove(id);
+        self.metrics.remove(&id.to_string());
+        self.limiter.remove(id);
+    }
+
+    /// 插件调用的统计，以插件 id 为目标；事件记为 `on_event` 方法，未声明的方法记为 [OTHER_METHOD]
+    pub fn metrics(&self) -> &CallMetrics {
+        &self.metrics
+    }
+
+    /// 检查宿主 API 版本，以及依赖的插件均已加载且版本符合要求
+    fn check_requirements(&self, info: &PluginInfo) -> Result<(), PluginError> {
+        if let (Some(req), Some(host)) = (&info.host, &self.host_api)
+            && !parse_requirement(req)?.matches(host)
+        {
+            return Err(PluginError::IncompatibleHost(format!(
+                "{req}, current {host}"
+            )));
+        }
+        for (name, req) in &info.dependencies {
+            let req = parse_requirement(req)?;
+            let version = self
+                .plugins
+                .iter()
+                .find(|p| &p.info.name == name)
+                .map(|p| p.info.version.clone())
+                .ok_or_else(|| PluginError::MissingDependency(format!("{name} {req}")))?;
+            if !req.matches(&parse_version(&version)?) {
+                return Err(PluginError::DependencyConflict(format!(
+                    "{} requires {name} {req}, found {version}",
+                    info.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查能否以 `info` 替换已加载的插件 `id`（如重新加载）：`info` 的要求均满足，
+    /// 且依赖原插件的已加载插件仍能找到符合其版本要求的插件
+    pub fn check_replace(&self, id: &PluginId, info: &PluginInfo) -> Result<(), PluginError> {
+        self.check_requirements(info)?;
+        let Some(name) = self.plugins.get(id).map(|p| p.info.name.clone()) else {
+            return Ok(());
+        };
+        let version = parse_version(&info.version)?;
+        for dependent in self.dependents(id) {
+            let Some(dependent) = self.plugins.get(&dependent) else {
+                continue;
+            };
+            let Some(req) = dependent.info.dependencies.get(&name) else {
+                continue;
+            };
+            if info.name != name {
+                return Err(PluginError::MissingDependency(format!(
+                    "{} requires {name} {req}",
+                    dependent.info.name
+                )));
+            }
+            if !parse_requirement(req)?.matches(&version) {
+                return Err(PluginError::DependencyConflict(format!(
+                    "{} requires {name} {req}, found {}",
+                    dependent.info.name, info.version
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// 依赖该插件的已加载插件
+    pub fn dependents(&self, id: &PluginId) -> Vec<PluginId> {
+        let Some(name) = self.plugins.get(id).map(|p| p.info.name.clone()) else {
+            return Vec::new();
+        };
+        self.plugins
+            .iter()
+            .filter(|p| p.info.dependencies.contains_key(&name))
+            .map(|p| p.key().clone())
+            .collect()
     }
 
     pub fn get(&self, id: &PluginId) -> Option<PluginInfo> {
@@ -65,27 +203,174 @@ impl PluginManager {
             .collect()
     }
 
+    /// 调用插件；`ctx` 为宿主上下文，传给插件前包装为该插件的上下文（见 [Caller]）。
+    /// 插件经由宿主调用其他插件时记录调用链，循环调用或嵌套过深时返回错误；
+    /// 超出并发限制的调用排队等待，队列已满时按 [CallLimits::overflow] 拒绝或等待
     pub async fn call(
         &self,
         id: &PluginId,
         arg: serde_json::Value,
         ctx: &dyn Context,
     ) -> PluginResult<serde_json::Value> {
-        let p = self.plugins.get(id).ok_or(PluginError::PluginNotFound)?;
-        p.load.plugin.call(arg, ctx).await
+        let (load, scoped) = self.scope(id, ctx)?;
+        let method = arg
+            .get("method")
+            .and_then(|m| m.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let (declared, serial) = self
+            .plugins
+            .get(id)
+            .map(|p| (p.info.declares(&method), p.info.serial.contains(&method)))
+            .unwrap_or_default();
+        let caller = &scoped.caller;
+        trace!("plugin {} call({}) {method}", caller.name, caller.call_id);
+        let label = if declared { &method } else { OTHER_METHOD };
+        let timer = self.metrics.start(&id.to_string(), label);
+        let result = async {
+            let _permit = self.limiter.acquire(id, &method, declared, serial).await?;
+            load.plugin.call(arg, &scoped).await
+        }
+        .await;
+        timer.finish(result.is_ok());
+        result
+    }
+
+    /// 向插件发送事件，上下文与 [PluginManager::call] 相同
+    pub async fn notify(&self, id: &PluginId, event: Event, ctx: &dyn Context) -> PluginResult<()> {
+        let (load, scoped) = self.scope(id, ctx)?;
+        let caller = &scoped.caller;
+        trace!(
+            "plugin {} call({}) on_event {}",
+            caller.name, caller.call_id, event.topic
+        );
+        let timer = self.metrics.start(&id.to_string(), "on_event");
+        let result = load.plugin.on_event(event, &scoped).await;
+        timer.finish(result.is_ok());
+        result
+    }
+
+    fn scope<'a>(
+        &self,
+        id: &PluginId,
+        ctx: &'a dyn Context,
+    ) -> Result<(LoadPlugin, ScopedContext<'a>), PluginError> {
+        // 不持有表项的引用，避免插件回调宿主时加载/卸载插件造成死锁
+        let (load, info) = {
+            let p = self.plugins.get(id).ok_or(PluginError::PluginNotFound)?;
+            (p.load.clone(), p.info.clone())
+        };
+        // 在插件回调宿主期间再次调用插件时，当前调用方即为上一级
+        let chain = match Caller::current() {
+            Some(parent) => {
+                let mut chain = parent.chain;
+                chain.push(parent.plugin);
+                chain
+            }
+            None => Vec::new(),
+        };
+        if chain.contains(id) {
+            return Err(PluginError::CallCycle(call_path(&chain, id)));
+        }
+        if chain.len() >= MAX_CALL_DEPTH {
+            return Err(PluginError::CallTooDeep(call_path(&chain, id)));
+        }
+        let scoped = ScopedContext {
+            host: ctx,
+            caller: Caller {
+                plugin: id.clone(),
+                name: info.name,
+                version: info.version,
+                call_id: self.next_call.fetch_add(1, Ordering::Relaxed),
+                chain,
+            },
+        };
+        Ok((load, scoped))
     }
 }
 
+fn call_path(chain: &[PluginId], id: &PluginId) -> String {
+    chain
+        .iter()
+        .chain([id])
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
 impl LoadPlugin {
     pub(crate) fn load(path: impl AsRef<str>) -> Result<Self, PluginError> {
         let lib = unsafe { libloading::Library::new(path.as_ref()) }?;
+        // 接口版本不一致时调用插件会导致未定义行为，须在创建插件对象前检查
+        let abi = unsafe { lib.get::<unsafe fn() -> u32>(NAME_ABI_FN.as_bytes()) }
+            .map(|abi_fn| unsafe { abi_fn() })
+            .map_err(|_| {
+                PluginError::AbiMismatch(format!("unknown, host {}", plugin::ABI_VERSION))
+            })?;
+        if abi != plugin::ABI_VERSION {
+            return Err(PluginError::AbiMismatch(format!(
+                "{abi}, host {}",
+                plugin::ABI_VERSION
+            )));
+        }
         let plugin_fn = unsafe { lib.get::<PluginFn>(NAME_PLUGIN_FN.as_bytes()) }?;
         let plugin = unsafe { plugin_fn() };
         Ok(Self {
-            _lib: Arc::new(lib),
             plugin: Arc::new(plugin),
+            _lib: Arc::new(lib),
+            _copy: None,
         })
     }
+
+    /// 将校验过的内容写入私有副本并从副本加载
+    fn load_verified(libfile: &str, lib: &[u8]) -> Result<Self, PluginError> {
+        let (copy, path) = PrivateCopy::new(libfile, lib)?;
+        let mut load = Self::load(path.to_string_lossy())?;
+        load._copy = Some(Arc::new(copy));
+        Ok(load)
+    }
+}
+
+impl PrivateCopy {
+    /// 返回副本及副本中库文件的路径，文件名与原文件相同
+    fn new(libfile: &str, lib: &[u8]) -> Result<(Self, PathBuf), PluginError> {
+        let name = common::random_hex();
+        let dir = std::env::temp_dir().join(format!("start-plugin-{name}"));
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&dir)?;
+        let copy = Self { dir };
+        let libfile = Path::new(libfile);
+        if let Some(source) = libfile.parent().filter(|p| !p.as_os_str().is_empty()) {
+            copy_dir(source, &copy.dir)?;
+        }
+        let file_name = libfile.file_name().unwrap_or("plugin".as_ref());
+        let path = copy.dir.join(file_name);
+        std::fs::write(&path, lib)?;
+        Ok((copy, path))
+    }
+}
+
+/// 复制文件夹内容；指向文件的链接按其内容复制（如 `libfoo.so -> libfoo.so.1`），指向文件夹的链接跳过
+fn copy_dir(source: &Path, dest: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            std::fs::create_dir(&target)?;
+            copy_dir(&entry.path(), &target)?;
+        } else if entry.path().is_file() {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+impl Drop for PrivateCopy {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
 }
 
 impl From<&PluginInfo> for PluginId {
@@ -0,0 +1,260 @@
+use crate::{PluginError, PluginInfo};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier as _, VerifyingKey};
+use libcommon::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Component, Path};
+
+/// 签名内容的版本前缀，修改签名内容格式时递增
+const SIGNING_CONTEXT: &str = "start-plugin-v2";
+
+/// 签名校验策略；默认只警告，未签名的已有插件升级后仍可加载，信任发布者后再改为 `Enforce`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignaturePolicy {
+    /// 校验失败时拒绝加载
+    Enforce,
+    /// 校验失败时只输出警告
+    #[default]
+    Warn,
+    /// 不校验
+    Off,
+}
+
+/// 在加载动态库之前校验插件签名
+///
+/// 签名为发布者私钥对 `start-plugin-v2\n{规范化的描述}` 的 ed25519 签名，与公钥一起以 base64
+/// 写在描述文件的 `signature`/`publisher` 中。规范化的描述为键按字母排序的紧凑 JSON，包含除
+/// `publisher`/`signature` 外的全部字段：`libfile` 替换为库文件的 sha256，本地的 `uiurl` 替换为
+/// 相对于库文件所在文件夹的路径，使安装到其他位置后签名仍然有效
+#[derive(Debug, Clone, Default)]
+pub struct Verifier {
+    policy: SignaturePolicy,
+    trusted: Vec<VerifyingKey>,
+}
+
+impl Verifier {
+    /// `keys` 为受信任的发布者公钥（base64）
+    pub fn new<S: AsRef<str>>(
+        policy: SignaturePolicy,
+        keys: impl IntoIterator<Item = S>,
+    ) -> Result<Self, PluginError> {
+        let trusted = keys
+            .into_iter()
+            .map(|key| decode_key(key.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { policy, trusted })
+    }
+
+    pub fn policy(&self) -> SignaturePolicy {
+        self.policy
+    }
+
+    /// 按策略校验，`Warn` 时校验失败也返回 Ok；`lib` 为库文件的内容，须与随后加载的内容一致
+    pub fn check(&self, info: &PluginInfo, lib: &[u8]) -> Result<(), PluginError> {
+        if self.policy == SignaturePolicy::Off {
+            return Ok(());
+        }
+        match self.verify(info, lib) {
+            Err(e) if self.policy == SignaturePolicy::Warn => {
+                warn!("Plugin {} signature check failed: {e}", info.name);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// 校验签名，不考虑策略
+    pub fn verify(&self, info: &PluginInfo, lib: &[u8]) -> Result<(), PluginError> {
+        let (Some(publisher), Some(signature)) = (&info.publisher, &info.signature) else {
+            return Err(PluginError::Unsigned);
+        };
+        let key = decode_key(publisher)?;
+        if !self.trusted.contains(&key) {
+            return Err(PluginError::UntrustedPublisher(publisher.clone()));
+        }
+        let signature = STANDARD
+            .decode(signature)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or_else(|| PluginError::InvalidSignature("malformed signature".to_string()))?;
+        let message = signing_message(info, lib);
+        key.verify(&message, &signature)
+            .map_err(|_| PluginError::InvalidSignature("signature does not match".to_string()))
+    }
+}
+
+/// 用发布者私钥（32 字节种子）签名，返回 (publisher, signature)，均为 base64；
+/// `info` 中的路径须已转换为绝对路径（见 [PluginInfo::canonicalize_by_parent]）
+pub fn sign(info: &PluginInfo, secret: &[u8; 32]) -> Result<(String, String), PluginError> {
+    let lib = std::fs::read(&info.libfile)
+        .map_err(|_| PluginError::UnExistResource(info.libfile.clone()))?;
+    let key = SigningKey::from_bytes(secret);
+    let signature = key.sign(&signing_message(info, &lib));
+    Ok((
+        STANDARD.encode(key.verifying_key().as_bytes()),
+        STANDARD.encode(signature.to_bytes()),
+    ))
+}
+
+fn signing_message(info: &PluginInfo, lib: &[u8]) -> Vec<u8> {
+    let uiurl = if info.uiurl.starts_with("http://") || info.uiurl.starts_with("https://") {
+        info.uiurl.clone()
+    } else {
+        let libdir = Path::new(&info.libfile).parent().unwrap_or(Path::new(""));
+        relative_to(Path::new(&info.uiurl), libdir)
+    };
+    // serde_json 的对象按键排序，序列化结果唯一
+    let canonical = serde_json::json!({
+        "name": info.name,
+        "version": info.version,
+        "libfile": format!("{:x}", Sha256::digest(lib)),
+        "uiurl": uiurl,
+        "spa": info.spa,
+        "permissions": info.permissions,
+        "exports": info.exports,
+        "serial": info.serial,
+        "dependencies": info.dependencies,
+        "host": info.host,
+    });
+    format!("{SIGNING_CONTEXT}\n{canonical}").into_bytes()
+}
+
+/// `path` 相对于 `base` 的路径（只按路径字面计算，`/` 分隔），如 `/a/ui` 相对于 `/a/lib/x` 为 `../../ui`
+fn relative_to(path: &Path, base: &Path) -> String {
+    let normal = |p: &Path| {
+        p.components()
+            .filter(|c| *c != Component::CurDir)
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+    };
+    let (path, base) = (normal(path), normal(base));
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut parts = vec![".."; base.len() - common];
+    parts.extend(path[common..].iter().map(String::as_str));
+    parts.join("/")
+}
+
+fn decode_key(key: &str) -> Result<VerifyingKey, PluginError> {
+    STANDARD
+        .decode(key)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| PluginError::InvalidPublisherKey(key.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: [u8; 32] = [7; 32];
+    const LIB: &[u8] = b"library";
+
+    fn info() -> PluginInfo {
+        serde_json::from_value(serde_json::json!({
+            "name": "demo",
+            "version": "1.0.0",
+            "libfile": "/plugins/demo/lib/libdemo.so",
+            "uiurl": "/plugins/demo/ui",
+            "exports": ["echo"],
+        }))
+        .unwrap()
+    }
+
+    fn signed(mut info: PluginInfo, lib: &[u8]) -> PluginInfo {
+        let key = SigningKey::from_bytes(&SECRET);
+        let signature = key.sign(&signing_message(&info, lib));
+        info.publisher = Some(publisher());
+        info.signature = Some(STANDARD.encode(signature.to_bytes()));
+        info
+    }
+
+    fn publisher() -> String {
+        STANDARD.encode(SigningKey::from_bytes(&SECRET).verifying_key().as_bytes())
+    }
+
+    fn verifier(policy: SignaturePolicy) -> Verifier {
+        Verifier::new(policy, [publisher()]).unwrap()
+    }
+
+    #[test]
+    fn valid_signature() {
+        let info = signed(info(), LIB);
+        assert!(
+            verifier(SignaturePolicy::Enforce)
+                .verify(&info, LIB)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn signature_survives_relocation() {
+        let info = signed(info(), LIB);
+        let mut moved = info.clone();
+        moved.libfile = "/opt/other/lib/libdemo.so".to_string();
+        moved.uiurl = "/opt/other/ui".to_string();
+        assert!(
+            verifier(SignaturePolicy::Enforce)
+                .verify(&moved, LIB)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let verifier = verifier(SignaturePolicy::Enforce);
+        let info = signed(info(), LIB);
+        assert!(matches!(
+            verifier.verify(&info, b"other library"),
+            Err(PluginError::InvalidSignature(_))
+        ));
+        let mut exports = info.clone();
+        exports.exports.push("admin".to_string());
+        assert!(matches!(
+            verifier.verify(&exports, LIB),
+            Err(PluginError::InvalidSignature(_))
+        ));
+        let mut permissions = info;
+        permissions.permissions.push("HostFs".to_string());
+        assert!(matches!(
+            verifier.verify(&permissions, LIB),
+            Err(PluginError::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn unsigned_and_untrusted() {
+        let verifier = verifier(SignaturePolicy::Enforce);
+        assert!(matches!(
+            verifier.verify(&info(), LIB),
+            Err(PluginError::Unsigned)
+        ));
+        let untrusted = Verifier::new(SignaturePolicy::Enforce, Vec::<String>::new()).unwrap();
+        assert!(matches!(
+            untrusted.verify(&signed(info(), LIB), LIB),
+            Err(PluginError::UntrustedPublisher(_))
+        ));
+    }
+
+    #[test]
+    fn policy() {
+        assert_eq!(Verifier::default().policy(), SignaturePolicy::Warn);
+        assert!(
+            verifier(SignaturePolicy::Enforce)
+                .check(&info(), LIB)
+                .is_err()
+        );
+        assert!(verifier(SignaturePolicy::Warn).check(&info(), LIB).is_ok());
+        assert!(verifier(SignaturePolicy::Off).check(&info(), LIB).is_ok());
+    }
+
+    #[test]
+    fn relative_paths() {
+        let rel = |path: &str, base: &str| relative_to(Path::new(path), Path::new(base));
+        assert_eq!(rel("/a/ui", "/a/lib/x"), "../../ui");
+        assert_eq!(rel("/a/ui", "/a"), "ui");
+        assert_eq!(rel("/a/./ui", "/a"), "ui");
+    }
+}
@@ -1,7 +1,8 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{
-    Ident, ItemFn, Pat, PatTupleStruct, PatType, ReturnType, Signature, Type, parse_macro_input,
+    GenericArgument, Ident, ItemFn, Pat, PatTupleStruct, PatType, PathArguments, PathSegment,
+    ReturnType, Signature, Type, parse_macro_input,
 };
 
 const WRAPPER_SUFFIX: &str = "_generate";
@@ -17,6 +18,8 @@ fn new_name(name: &Ident) -> Ident {
 ///
 /// 返回值可以为 `serde_json::Value`，也可以是 `std::result::Result<serde_json::Value, Box<dyn std::error::Error>>`;
 /// 如果返回值不是 `Result`, 要保证当前简写的 `Result` 的最后一段路径名是 `Result`;
+/// 返回 `Vec<u8>` 或 `Bytes`（包括在 `Result` 中）时不经 JSON 编码，通过二进制通道发送，前端收到 `ArrayBuffer`；
+/// 返回 `window::Reply` 时原样返回，可在运行时选择 JSON 或二进制。
 ///
 /// 支持宿主状态参数：如果原函数最后一个参数类型为 `WindowState<H>`，则将其视为宿主状态，不会出现在参数结构体中，
 /// 并在生成的包装函数中通过第二个参数传入。参数模式可以是 `state: WindowState<H>` 或 `WindowState(state): WindowState<H>`。
@@ -46,7 +49,7 @@ fn new_name(name: &Ident) -> Ident {
 ///     use super::*;
 ///     pub fn _add_generate(
 ///         _arg: Option<Box<serde_json::value::RawValue>>, state: WindowState<MyState>,
-///     ) -> Pin<Box<dyn Future<Output = std::result::Result<serde_json::Value, Box<dyn std::error::Error>>> + Send>> {
+///     ) -> Pin<Box<dyn Future<Output = std::result::Result<::window::Reply, Box<dyn std::error::Error>>> + Send>> {
 ///         Box::pin(async move {
 ///             let raw = _arg.ok_or_else(|| Box::<dyn std::error::Error>::from("need args but got none"))?;
 ///             #[derive(serde::Deserialize)]
@@ -56,7 +59,7 @@ fn new_name(name: &Ident) -> Ident {
 ///             }
 ///             let Args { a, b } = serde_json::from_str(raw.get())?;
 ///             let result = super::add(a, b, state).await?;
-///             Ok(serde_json::to_value(result).unwrap())
+///             Ok(::window::Reply::Json(serde_json::json!(result)))
 ///         })
 ///     }
 /// }
@@ -92,6 +95,7 @@ fn expand(input: ItemFn) -> Result<TokenStream, syn::Error> {
     // 准备代码生成所需的辅助信息
     let is_async = sig.asyncness.is_some();
     let returns_result = is_return_result(sig);
+    let reply_ts = build_reply(sig);
     let state_arg_ts = if had_state {
         quote! { _state }
     } else {
@@ -99,7 +103,14 @@ fn expand(input: ItemFn) -> Result<TokenStream, syn::Error> {
     };
 
     // 生成调用原函数并包装结果的代码块
-    let call_body = build_call_body(&params, is_async, returns_result, ident, &state_arg_ts);
+    let call_body = build_call_body(
+        &params,
+        is_async,
+        returns_result,
+        ident,
+        &state_arg_ts,
+        &reply_ts,
+    );
 
     // 生成包装函数
     let generate_name = new_name(ident);
@@ -114,7 +125,7 @@ fn expand(input: ItemFn) -> Result<TokenStream, syn::Error> {
         pub mod #ident {
             use super::*;
             pub fn #generate_name #generics (_arg: Option<Box<serde_json::value::RawValue>>, #state_param)
-                -> std::pin::Pin<Box<dyn Future<Output = std::result::Result<serde_json::Value, Box<dyn std::error::Error>>> + Send>>
+                -> std::pin::Pin<Box<dyn Future<Output = std::result::Result<::window::Reply, Box<dyn std::error::Error>>> + Send>>
             {
                 Box::pin(async move {
                     #call_body
@@ -189,6 +200,56 @@ fn is_return_result(sig: &Signature) -> bool {
     false
 }
 
+/// 返回值类型，为 `Result` 时取其第一个泛型参数
+fn return_value_type(sig: &Signature) -> Option<&Type> {
+    let ReturnType::Type(_, ty) = &sig.output else {
+        return None;
+    };
+    if let Type::Path(path) = ty.as_ref()
+        && let Some(segment) = path.path.segments.last()
+        && segment.ident == "Result"
+    {
+        return first_generic(segment);
+    }
+    Some(ty)
+}
+
+fn first_generic(segment: &PathSegment) -> Option<&Type> {
+    if let PathArguments::AngleBracketed(args) = &segment.arguments
+        && let Some(GenericArgument::Type(ty)) = args.args.first()
+    {
+        return Some(ty);
+    }
+    None
+}
+
+/// 类型路径的最后一段
+fn last_segment(ty: &Type) -> Option<&PathSegment> {
+    match ty {
+        Type::Path(path) => path.path.segments.last(),
+        _ => None,
+    }
+}
+
+/// 生成将返回值 `result` 转换为 `window::Reply` 的表达式
+fn build_reply(sig: &Signature) -> proc_macro2::TokenStream {
+    let segment = return_value_type(sig).and_then(last_segment);
+    let is_bytes = segment.is_some_and(|s| {
+        s.ident == "Bytes"
+            || (s.ident == "Vec"
+                && first_generic(s)
+                    .and_then(last_segment)
+                    .is_some_and(|s| s.ident == "u8"))
+    });
+    if is_bytes {
+        quote! { ::window::Reply::binary(result) }
+    } else if segment.is_some_and(|s| s.ident == "Reply") {
+        quote! { result }
+    } else {
+        quote! { ::window::Reply::Json(serde_json::json!(result)) }
+    }
+}
+
 /// 生成调用原函数并包装结果的代码块
 fn build_call_body(
     params: &[(Ident, &Type)],
@@ -196,6 +257,7 @@ fn build_call_body(
     returns_result: bool,
     fn_ident: &Ident,
     state_arg_ts: &proc_macro2::TokenStream,
+    reply_ts: &proc_macro2::TokenStream,
 ) -> proc_macro2::TokenStream {
     let await_ts = if is_async {
         quote! { .await }
@@ -212,7 +274,7 @@ fn build_call_body(
     if params.is_empty() {
         quote! {
             let result = super::#fn_ident(#state_arg_ts) #await_ts #try_ts;
-            Ok(serde_json::json!(result))
+            Ok(#reply_ts)
         }
     } else {
         // 生成 Args 结构体定义, 因为前端是统一传json的
@@ -229,7 +291,7 @@ fn build_call_body(
             }
             let Args { #(#field_names,)* } = serde_json::from_str(raw.get())?;
             let result = super::#fn_ident(#(#field_names2,)* #state_arg_ts) #await_ts #try_ts;
-            Ok(serde_json::json!(result))
+            Ok(#reply_ts)
         }
     }
 }
@@ -0,0 +1,105 @@
+use crate::Message;
+use dashmap::{DashMap, DashSet};
+use std::time::{Duration, Instant};
+
+/// 窗口中读取二进制响应的自定义协议，地址为 `bridge://localhost/<id>`（Windows 和 Android 为 `http://bridge.localhost/<id>`）
+pub const BLOB_PROTOCOL: &str = "bridge";
+/// 未被读取的二进制响应的保留时间
+const BLOB_TTL: Duration = Duration::from_secs(60);
+/// 未被读取的二进制响应的总大小上限，超出时先移除最早的
+const MAX_TOTAL_SIZE: usize = 256 * 1024 * 1024;
+const DEFAULT_MIME: &str = "application/octet-stream";
+
+/// 命令的返回值
+#[derive(Debug)]
+pub enum Reply {
+    Json(Message),
+    /// 不经 JSON 编码，通过二进制通道发送，前端收到 `ArrayBuffer`
+    Binary {
+        mime: String,
+        data: Vec<u8>,
+    },
+}
+
+impl Reply {
+    pub fn binary(data: impl Into<Vec<u8>>) -> Self {
+        Self::Binary {
+            mime: DEFAULT_MIME.to_string(),
+            data: data.into(),
+        }
+    }
+}
+
+/// 等待前端读取的二进制响应，读取一次后移除；id 为 128 位随机数，无法猜测
+#[derive(Default)]
+pub struct Blobs {
+    blobs: DashMap<String, Blob>,
+    origins: DashSet<String>,
+}
+
+struct Blob {
+    mime: String,
+    data: Vec<u8>,
+    created: Instant,
+}
+
+impl Blobs {
+    /// 保存二进制响应，返回读取用的 id；同时清除超时未读取的响应，总大小超出上限时清除最早的响应
+    pub fn insert(&self, mime: String, data: Vec<u8>) -> String {
+        self.evict(data.len());
+        let id = common::random_hex();
+        let created = Instant::now();
+        self.blobs.insert(
+            id.clone(),
+            Blob {
+                mime,
+                data,
+                created,
+            },
+        );
+        id
+    }
+
+    /// 取出二进制响应及其 MIME 类型
+    pub fn take(&self, id: &str) -> Option<(String, Vec<u8>)> {
+        self.blobs
+            .remove(id)
+            .filter(|(_, b)| b.created.elapsed() < BLOB_TTL)
+            .map(|(_, b)| (b.mime, b.data))
+    }
+
+    /// 允许该来源（如 `http://127.0.0.1:3030`）的页面通过自定义协议跨域读取
+    pub fn allow_origin(&self, origin: impl Into<String>) {
+        self.origins.insert(origin.into());
+    }
+
+    pub(crate) fn allows(&self, origin: &str) -> bool {
+        self.origins.contains(origin)
+    }
+
+    /// 为新增的 `incoming` 字节腾出空间
+    fn evict(&self, incoming: usize) {
+        self.blobs.retain(|_, b| b.created.elapsed() < BLOB_TTL);
+        let mut total: usize = self.blobs.iter().map(|b| b.data.len()).sum();
+        while total + incoming > MAX_TOTAL_SIZE {
+            let oldest = self
+                .blobs
+                .iter()
+                .min_by_key(|b| b.created)
+                .map(|b| b.key().clone());
+            match oldest.and_then(|id| self.blobs.remove(&id)) {
+                Some((_, b)) => total -= b.data.len(),
+                None => break,
+            }
+        }
+    }
+}
+
+/// 前端读取二进制响应的地址前缀，与 wry 对自定义协议的处理一致
+pub(crate) fn blob_base_url() -> String {
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{BLOB_PROTOCOL}.localhost/")
+    } else {
+        format!("{BLOB_PROTOCOL}://localhost/")
+    }
+}
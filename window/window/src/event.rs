@@ -2,13 +2,21 @@ use crate::{Message, WindowId};
 use libcommon::warn;
 use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
+use std::sync::{Arc, Mutex};
 use tao::event_loop::EventLoopProxy;
 
 #[derive(Debug)]
 pub(crate) enum UserEvent {
     IpcMessage(WindowId, String),
     SysWindowEvent(WindowId, SysWindowEvent),
-    IcpResultSend(WindowId, IpcResp),
+    /// 命令的响应，在事件循环的每一轮结束时合并发送给窗口
+    IcpResultSend(WindowId, Vec<IpcResp>),
+    /// 向所有窗口的前端发送事件
+    Emit(String, Message),
+    /// 向指定窗口的前端发送事件
+    EmitTo(WindowId, String, Message),
+    /// 显示（并聚焦）或隐藏窗口
+    SetVisible(WindowId, bool),
 }
 
 unsafe impl Send for UserEvent {}
@@ -20,6 +28,48 @@ pub(crate) enum SysWindowEvent {
     Minimize,
 }
 
+/// 窗口生命周期事件，见 [WindowManager::with_listener](crate::WindowManager::with_listener)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum WindowLifecycle {
+    Created,
+    Focused,
+    Blurred,
+    Minimized,
+    Closed,
+}
+
+/// 从后端向前端发送事件，前端通过 `window.bridge.on(event, callback)` 接收
+#[derive(Clone)]
+pub struct Emitter {
+    proxy: Arc<Mutex<EventLoopProxy<UserEvent>>>,
+}
+
+impl Emitter {
+    pub(crate) fn new(proxy: EventLoopProxy<UserEvent>) -> Self {
+        Self {
+            proxy: Arc::new(Mutex::new(proxy)),
+        }
+    }
+
+    pub fn emit(&self, event: impl Into<String>, payload: Message) {
+        let proxy = self.proxy.lock().unwrap_or_else(|e| e.into_inner());
+        UserEvent::Emit(event.into(), payload).send(&proxy);
+    }
+
+    /// 只发送给指定窗口，窗口不存在时丢弃
+    pub fn emit_to(&self, window: &WindowId, event: impl Into<String>, payload: Message) {
+        let proxy = self.proxy.lock().unwrap_or_else(|e| e.into_inner());
+        UserEvent::EmitTo(window.clone(), event.into(), payload).send(&proxy);
+    }
+
+    /// 显示（并聚焦）或隐藏窗口，窗口不存在时忽略
+    pub fn set_visible(&self, window: &WindowId, visible: bool) {
+        let proxy = self.proxy.lock().unwrap_or_else(|e| e.into_inner());
+        UserEvent::SetVisible(window.clone(), visible).send(&proxy);
+    }
+}
+
 impl UserEvent {
     pub(crate) fn send(self, proxy: &EventLoopProxy<UserEvent>) {
         if proxy.send_event(self).is_err() {
@@ -28,6 +78,43 @@ impl UserEvent {
     }
 }
 
+/// 批量请求的请求数上限，超出时整批返回错误而不执行
+pub(crate) const MAX_BATCH: usize = 32;
+
+/// 前端发来的消息：单个 [IpcReq]，或由多个 [IpcReq] 组成的 JSON 数组（批量请求，响应也为数组）
+#[derive(Debug)]
+pub(crate) enum IpcMsg {
+    Single(IpcReq),
+    Batch(Vec<IpcReq>),
+}
+
+impl IpcMsg {
+    /// 处理该消息需要执行的命令数，超出上限的批量请求不执行、只计为 1
+    pub(crate) fn cost(&self) -> usize {
+        match self {
+            IpcMsg::Batch(reqs) if reqs.len() <= MAX_BATCH => reqs.len().max(1),
+            _ => 1,
+        }
+    }
+
+    /// 消息中各请求的 id
+    pub(crate) fn ids(&self) -> Vec<u32> {
+        match self {
+            IpcMsg::Single(req) => vec![req.id],
+            IpcMsg::Batch(reqs) => reqs.iter().map(|r| r.id).collect(),
+        }
+    }
+
+    /// `RawValue` 不支持 untagged 枚举，按首个字符区分
+    pub(crate) fn parse(msg: &str) -> serde_json::Result<Self> {
+        if msg.trim_start().starts_with('[') {
+            serde_json::from_str(msg).map(IpcMsg::Batch)
+        } else {
+            serde_json::from_str(msg).map(IpcMsg::Single)
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct IpcReq {
     pub(crate) id: u32,
@@ -43,6 +130,12 @@ pub(crate) struct IpcResp {
     pub(crate) payload: Option<Message>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) error: Option<String>,
+    /// 错误码，见 [CommandError](crate::CommandError)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) code: Option<String>,
+    /// 二进制响应的 id，见 [Reply::Binary](crate::Reply::Binary)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) blob: Option<String>,
 }
 
 impl IpcResp {
@@ -51,14 +144,28 @@ impl IpcResp {
             id,
             payload: Some(payload),
             error: None,
+            code: None,
+            blob: None,
+        }
+    }
+
+    pub fn blob(id: u32, blob: String) -> Self {
+        Self {
+            id,
+            payload: None,
+            error: None,
+            code: None,
+            blob: Some(blob),
         }
     }
 
-    pub fn err(id: u32, error: String) -> Self {
+    pub fn err(id: u32, error: String, code: Option<&str>) -> Self {
         Self {
             id,
             payload: None,
             error: Some(error),
+            code: code.map(str::to_string),
+            blob: None,
         }
     }
 }
@@ -0,0 +1,355 @@
+use crate::{
+    Blobs, FnResult, RawMessage, Reply, WindowId, WindowState,
+    event::{IpcMsg, IpcReq, IpcResp, MAX_BATCH},
+};
+use dashmap::DashMap;
+use futures_util::{StreamExt, stream};
+use libcommon::prelude::*;
+use std::{
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// 批量请求中同时执行的命令数上限
+const BATCH_CONCURRENCY: usize = 8;
+
+/// 内部存储动态分发的类型
+type BoxedHandler<H> = Box<
+    dyn Fn(Option<RawMessage>, WindowState<H>) -> Pin<Box<dyn Future<Output = FnResult> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// [bridge](crate::bridge) 命令表
+///
+/// 窗口的 IPC 与宿主的 HTTP/WebSocket 接口共用同一份命令表，无窗口时也可调用
+pub struct BridgeHandlers<H> {
+    handlers: Arc<DashMap<String, Arc<BoxedHandler<H>>>>,
+    access: Arc<DashMap<String, Access>>,
+    observer: Arc<OnceLock<Arc<dyn BridgeObserver>>>,
+    blobs: Arc<Blobs>,
+}
+
+/// 命令的调用来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// 窗口的 IPC
+    Window(WindowId),
+    /// 窗口之外的通道，如宿主的 HTTP/WebSocket 接口
+    Remote,
+}
+
+tokio::task_local! {
+    static SOURCE: Source;
+}
+
+impl Source {
+    /// 正在执行的命令的调用来源，只在命令执行期间（见 [BridgeHandlers::call]）有效
+    pub fn current() -> Option<Source> {
+        SOURCE.try_with(Clone::clone).ok()
+    }
+}
+
+/// 命令允许的调用来源，见 [BridgeHandlers::restrict]；未设置的命令为 [Access::Windows]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Access {
+    /// 任意来源，包括窗口之外的通道
+    Any,
+    /// 任意窗口的 IPC
+    Windows,
+    /// 指定窗口的 IPC
+    Window(WindowId),
+}
+
+impl Access {
+    fn allows(&self, source: &Source) -> bool {
+        match (self, source) {
+            (Access::Any, _) => true,
+            (Access::Windows, Source::Window(_)) => true,
+            (Access::Window(id), Source::Window(from)) => id == from,
+            _ => false,
+        }
+    }
+}
+
+/// 命令开始和结束时的回调，如用于统计调用次数和耗时
+pub trait BridgeObserver: Send + Sync {
+    fn start(&self, command: &str);
+    /// 命令未完成即被取消时 `ok` 为 false
+    fn finish(&self, command: &str, elapsed: Duration, ok: bool);
+}
+
+/// 命令未完成即被释放时通知观察者
+struct Observing<'a> {
+    observer: Option<&'a Arc<dyn BridgeObserver>>,
+    command: &'a str,
+    start: Instant,
+}
+
+impl Observing<'_> {
+    fn finish(mut self, ok: bool) {
+        if let Some(observer) = self.observer.take() {
+            observer.finish(self.command, self.start.elapsed(), ok);
+        }
+    }
+}
+
+impl Drop for Observing<'_> {
+    fn drop(&mut self) {
+        if let Some(observer) = self.observer.take() {
+            observer.finish(self.command, self.start.elapsed(), false);
+        }
+    }
+}
+
+/// 命令调用失败的原因
+#[derive(Debug)]
+pub enum BridgeError {
+    /// 命令未注册
+    NoHandler(String),
+    /// 调用来源不允许调用该命令，见 [BridgeHandlers::restrict]
+    Forbidden(String),
+    /// 命令执行出错
+    Handler(String),
+    /// 命令返回了带错误码的 [CommandError]
+    Coded { code: String, message: String },
+}
+
+/// 带错误码的命令错误，[bridge](crate::bridge) 函数返回该错误时前端可按 `code` 区分处理
+#[derive(Debug)]
+pub struct CommandError {
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl CommandError {
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: Some(code.into()),
+            message: message.into(),
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self {
+            code: None,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl<H> Clone for BridgeHandlers<H> {
+    fn clone(&self) -> Self {
+        Self {
+            handlers: self.handlers.clone(),
+            access: self.access.clone(),
+            observer: self.observer.clone(),
+            blobs: self.blobs.clone(),
+        }
+    }
+}
+
+impl<H> Default for BridgeHandlers<H> {
+    fn default() -> Self {
+        Self {
+            handlers: Default::default(),
+            access: Default::default(),
+            observer: Default::default(),
+            blobs: Default::default(),
+        }
+    }
+}
+
+impl<H: Send + Sync + 'static> BridgeHandlers<H> {
+    pub fn register<I, F>(&self, handlers: I)
+    where
+        I: IntoIterator<Item = (String, F)>,
+        F: Fn(
+                Option<RawMessage>,
+                WindowState<H>,
+            ) -> Pin<Box<dyn Future<Output = FnResult> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        for (name, ele) in handlers {
+            self.handlers.insert(name, Arc::new(Box::new(ele)));
+        }
+    }
+
+    /// 设置命令的调用来源；未设置的命令只允许窗口调用，需通过 [Access::Any] 开放给窗口之外的通道
+    pub fn restrict<I, S>(&self, commands: I, access: Access)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for command in commands {
+            self.access.insert(command.into(), access.clone());
+        }
+    }
+
+    /// 设置观察者，所有共用该命令表的通道均生效；只有第一次设置生效
+    pub fn set_observer(&self, observer: Arc<dyn BridgeObserver>) {
+        let _ = self.observer.set(observer);
+    }
+
+    /// 等待前端读取的二进制响应，见 [Reply::Binary]
+    pub fn blobs(&self) -> Arc<Blobs> {
+        self.blobs.clone()
+    }
+
+    /// 已注册的命令名
+    pub fn commands(&self) -> Vec<String> {
+        self.handlers.iter().map(|e| e.key().clone()).collect()
+    }
+
+    /// 调用命令，`source` 为调用来源
+    pub async fn call(
+        &self,
+        command: &str,
+        payload: Option<RawMessage>,
+        state: WindowState<H>,
+        source: &Source,
+    ) -> Result<Reply, BridgeError> {
+        let Some(fun) = self.handlers.get(command).map(|v| Arc::clone(&v)) else {
+            return Err(BridgeError::NoHandler(command.to_string()));
+        };
+        let allowed = match self.access.get(command) {
+            Some(access) => access.allows(source),
+            None => Access::Windows.allows(source),
+        };
+        if !allowed {
+            return Err(BridgeError::Forbidden(command.to_string()));
+        }
+        let observer = self.observer.get();
+        if let Some(observer) = observer {
+            observer.start(command);
+        }
+        let observing = Observing {
+            observer,
+            command,
+            start: Instant::now(),
+        };
+        let result = SOURCE
+            .scope(source.clone(), fun(payload, state))
+            .await
+            .map_err(|e| match e.downcast::<CommandError>() {
+                Ok(e) => match e.code {
+                    Some(code) => BridgeError::Coded {
+                        code,
+                        message: e.message,
+                    },
+                    None => BridgeError::Handler(e.message),
+                },
+                Err(e) => BridgeError::Handler(format!("{e:?}")),
+            });
+        observing.finish(result.is_ok());
+        result
+    }
+
+    /// 处理一条 [IpcReq] 格式的消息，返回 [IpcResp] 格式的响应；消息为 [IpcReq] 数组（批量请求）时返回响应数组。
+    /// 消息无法解析时返回 None
+    pub async fn dispatch(
+        &self,
+        msg: &str,
+        state: WindowState<H>,
+        source: &Source,
+    ) -> Option<String> {
+        let msg = match IpcMsg::parse(msg) {
+            Ok(msg) => msg,
+            Err(e) => {
+                warn!("Failed to parse ipc message: {e}, ignore.");
+                return None;
+            }
+        };
+        match msg {
+            IpcMsg::Single(ipcreq) => {
+                serde_json::to_string(&self.handle(ipcreq, state, source).await)
+            }
+            IpcMsg::Batch(_) => serde_json::to_string(&self.handle_msg(msg, state, source).await),
+        }
+        .ok()
+    }
+
+    /// 处理一条消息，批量请求中的命令至多 [BATCH_CONCURRENCY] 条同时执行，响应与请求顺序一致；
+    /// 批量请求超过 [MAX_BATCH] 条时每条请求均返回 `batch_too_large` 错误
+    pub(crate) async fn handle_msg(
+        &self,
+        msg: IpcMsg,
+        state: WindowState<H>,
+        source: &Source,
+    ) -> Vec<IpcResp> {
+        match msg {
+            IpcMsg::Single(ipcreq) => vec![self.handle(ipcreq, state, source).await],
+            IpcMsg::Batch(reqs) => {
+                trace!("receiver ipc batch of {}", reqs.len());
+                if reqs.len() > MAX_BATCH {
+                    let error = format!("batch of {} exceeds {MAX_BATCH} requests", reqs.len());
+                    return reqs
+                        .iter()
+                        .map(|req| IpcResp::err(req.id, error.clone(), Some("batch_too_large")))
+                        .collect();
+                }
+                stream::iter(reqs)
+                    .map(|req| self.handle(req, state.clone(), source))
+                    .buffered(BATCH_CONCURRENCY)
+                    .collect()
+                    .await
+            }
+        }
+    }
+
+    pub(crate) async fn handle(
+        &self,
+        ipcreq: IpcReq,
+        state: WindowState<H>,
+        source: &Source,
+    ) -> IpcResp {
+        trace!("receiver IpcMessage: {ipcreq:?}");
+        match self
+            .call(&ipcreq.command, ipcreq.payload, state, source)
+            .await
+        {
+            Ok(Reply::Json(res)) => IpcResp::ok(ipcreq.id, res),
+            // 二进制响应只返回 id，前端再通过自定义协议或 HTTP 读取
+            Ok(Reply::Binary { mime, data }) => {
+                IpcResp::blob(ipcreq.id, self.blobs.insert(mime, data))
+            }
+            Err(e) => IpcResp::err(ipcreq.id, e.to_string(), e.code()),
+        }
+    }
+}
+
+impl BridgeError {
+    /// 错误码，见 [CommandError]
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            BridgeError::Coded { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BridgeError::NoHandler(cmd) => write!(f, "No handler registered for command '{cmd}'"),
+            BridgeError::Forbidden(cmd) => write!(f, "Command '{cmd}' is not allowed from here"),
+            BridgeError::Handler(e) => write!(f, "{e}"),
+            BridgeError::Coded { message, .. } => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {}
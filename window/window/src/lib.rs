@@ -1,12 +1,17 @@
+mod blob;
 mod event;
+mod handler;
 mod script;
 mod window;
 mod wm;
 
 use std::pin::Pin;
 
+pub use blob::{BLOB_PROTOCOL, Blobs, Reply};
+pub use event::{Emitter, WindowLifecycle};
+pub use handler::*;
 pub use paste::paste;
-pub use tao::window::WindowBuilder;
+pub use tao::{dpi::LogicalSize, window::WindowBuilder};
 pub use window::*;
 pub use window_macro::bridge;
 pub use wm::*;
@@ -15,7 +20,7 @@ pub use wry::WebViewBuilder;
 pub type RawMessage = Box<serde_json::value::RawValue>;
 pub type Message = serde_json::Value;
 
-pub(crate) type FnResult = std::result::Result<Message, Box<dyn std::error::Error>>;
+pub(crate) type FnResult = std::result::Result<Reply, Box<dyn std::error::Error>>;
 
 pub type Handler<H> =
     fn(Option<RawMessage>, WindowState<H>) -> Pin<Box<dyn Future<Output = FnResult> + Send>>;
@@ -1,4 +1,7 @@
-use crate::event::SysWindowEvent;
+use crate::{
+    blob::blob_base_url,
+    event::{MAX_BATCH, SysWindowEvent},
+};
 
 /// 前端桥接对象挂载的全局变量名（内部使用，不对外暴露）
 pub const BRIDGE_INTERNAL: &str = "__bridge";
@@ -9,6 +12,9 @@ pub const BRIDGE_PUBLIC: &str = "bridge";
 /// 后端回调前端响应处理函数的方法名（挂载在内部桥接对象上）
 pub const BRIDGE_HANDLER_METHOD: &str = "_handleResponse";
 
+/// 后端向前端发送事件的方法名（挂载在内部桥接对象上）
+pub const BRIDGE_EVENT_METHOD: &str = "_handleEvent";
+
 pub const ERROR_PARAM_NAME: &str = "error";
 
 /// 完整的后端调用表达式，用于 evaluate_script
@@ -19,6 +25,15 @@ pub fn bridge_handler_call(response_json: &str) -> String {
         BRIDGE_INTERNAL, BRIDGE_HANDLER_METHOD, response_json
     )
 }
+/// 完整的后端事件调用表达式，参数均为 JSON
+/// 格式：window.__bridge._handleEvent(event, payload)
+pub fn bridge_event_call(event_json: &str, payload_json: &str) -> String {
+    format!(
+        "window.{}.{}({}, {});",
+        BRIDGE_INTERNAL, BRIDGE_EVENT_METHOD, event_json, payload_json
+    )
+}
+
 pub(crate) fn setup_script() -> String {
     let window_commands = window_commands_script();
 
@@ -28,18 +43,42 @@ pub(crate) fn setup_script() -> String {
   const BRIDGE = {{
     _nextId: 1,
     _callbacks: new Map(),
+    _listeners: new Map(),
     {handler}: function(response) {{
       response = typeof response === 'string' ? JSON.parse(response) : response;
+      if (Array.isArray(response)) {{
+        response.forEach(r => this.{handler}(r));
+        return;
+      }}
       const cb = this._callbacks.get(response.id);
       if (cb) {{
         this._callbacks.delete(response.id);
-        if (response.payload && typeof response.payload === 'object' && response.payload.{error}) {{
+        if (response.{error} !== undefined) {{
+          // 带错误码的错误（如插件调用被拒绝时为 busy）可通过 error.code 区分
+          cb.reject(Object.assign(new Error(response.{error}), {{ code: response.code }}));
+        }} else if (response.blob !== undefined) {{
+          // 二进制响应通过自定义协议读取
+          fetch('{blob_url}' + response.blob)
+            .then(r => r.ok ? r.arrayBuffer() : Promise.reject(new Error('blob ' + response.blob + ' not found')))
+            .then(cb.resolve, cb.reject);
+        }} else if (response.payload && typeof response.payload === 'object' && response.payload.{error}) {{
           cb.reject(new Error(response.payload.{error}));
         }} else {{
           cb.resolve(response.payload);
         }}
       }}
     }},
+    {event_handler}: function(event, payload) {{
+      (this._listeners.get(event) || []).forEach(cb => {{
+        try {{ cb(payload); }} catch (e) {{ console.error(e); }}
+      }});
+    }},
+    on: function(event, cb) {{
+      const list = this._listeners.get(event) || [];
+      list.push(cb);
+      this._listeners.set(event, list);
+      return () => this._listeners.set(event, (this._listeners.get(event) || []).filter(f => f !== cb));
+    }},
     send: function(command, payload) {{
       const id = this._nextId++;
       return new Promise((resolve, reject) => {{
@@ -47,6 +86,18 @@ pub(crate) fn setup_script() -> String {
         window.ipc.postMessage(JSON.stringify({{ id, command, payload }}));
       }});
     }},
+    // 批量发送命令：calls 为 {{ command, payload }} 数组（至多 {max_batch} 条），只发送一条消息；
+    // 返回与 calls 顺序一致的结果，格式同 Promise.allSettled
+    batch: function(calls) {{
+      const reqs = calls.map(({{ command, payload }}) => ({{ id: this._nextId++, command, payload }}));
+      const results = reqs.map(req => new Promise((resolve, reject) => {{
+        this._callbacks.set(req.id, {{ resolve, reject }});
+      }}));
+      if (reqs.length > 0) {{
+        window.ipc.postMessage(JSON.stringify(reqs));
+      }}
+      return Promise.allSettled(results);
+    }},
     sendRaw: function(command) {{
       window.ipc.postMessage(command);
     }}
@@ -54,7 +105,9 @@ pub(crate) fn setup_script() -> String {
   window.{internal} = BRIDGE;
   window.{public} = {{
     send: BRIDGE.send.bind(BRIDGE),
-    sendRaw: BRIDGE.sendRaw.bind(BRIDGE)
+    batch: BRIDGE.batch.bind(BRIDGE),
+    sendRaw: BRIDGE.sendRaw.bind(BRIDGE),
+    on: BRIDGE.on.bind(BRIDGE)
   }};
 
   {cmd}
@@ -63,7 +116,10 @@ pub(crate) fn setup_script() -> String {
         internal = BRIDGE_INTERNAL,
         public = BRIDGE_PUBLIC,
         handler = BRIDGE_HANDLER_METHOD,
+        event_handler = BRIDGE_EVENT_METHOD,
         error = ERROR_PARAM_NAME,
+        blob_url = blob_base_url(),
+        max_batch = MAX_BATCH,
         cmd = window_commands,
     )
 }
@@ -1,14 +1,21 @@
 use crate::{
+    BLOB_PROTOCOL, Blobs, Message,
     event::{IpcResp, SysWindowEvent, UserEvent},
     script,
 };
 use libcommon::{New, hash, prelude::*};
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc};
 use tao::{
     event_loop::{EventLoop, EventLoopProxy},
     window::{Window as TaoWindow, WindowBuilder},
 };
-use wry::{WebView as WryWebView, WebViewBuilder, http::Request};
+use wry::{
+    WebView as WryWebView, WebViewBuilder,
+    http::{
+        Request, Response, StatusCode,
+        header::{ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE, ORIGIN, VARY},
+    },
+};
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct WindowId(pub Arc<str>);
@@ -28,13 +35,26 @@ pub(crate) struct WindowRef {
 }
 
 impl WindowRef {
-    pub(crate) fn resp2web(&self, resp: IpcResp) -> Result<()> {
-        let json = serde_json::to_string(&resp)?;
+    /// 发送响应，多个响应以数组一次发送
+    pub(crate) fn resp2web(&self, mut resps: Vec<IpcResp>) -> Result<()> {
+        let json = match resps.len() {
+            0 => return Ok(()),
+            1 => serde_json::to_string(&resps.remove(0))?,
+            _ => serde_json::to_string(&resps)?,
+        };
         self.webview
             .evaluate_script(&script::bridge_handler_call(&json))?;
         Ok(())
     }
 
+    pub(crate) fn emit2web(&self, event: &str, payload: &Message) -> Result<()> {
+        let event = serde_json::to_string(event)?;
+        let payload = serde_json::to_string(payload)?;
+        self.webview
+            .evaluate_script(&script::bridge_event_call(&event, &payload))?;
+        Ok(())
+    }
+
     pub(crate) fn id(&self) -> WindowId {
         (&self.window).into()
     }
@@ -43,6 +63,7 @@ impl WindowRef {
         win: WindowBuilder,
         web: WebViewBuilder<'a>,
         event: &EventLoop<UserEvent>,
+        blobs: Arc<Blobs>,
     ) -> Result<Self> {
         let window = win.build(event)?;
         let wid: WindowId = (&window).into();
@@ -50,6 +71,9 @@ impl WindowRef {
         let webview = web
             .with_initialization_script(script::setup_script())
             .with_ipc_handler(move |req| Self::handle_ipc(&proxy, &wid, req))
+            .with_custom_protocol(BLOB_PROTOCOL.to_string(), move |_, req| {
+                blob_response(&blobs, &req)
+            })
             .build(&window)?;
         Ok(Self::new(window, webview))
     }
@@ -69,6 +93,36 @@ impl WindowRef {
     }
 }
 
+/// 自定义协议读取二进制响应，路径为 `/<id>`
+fn blob_response(blobs: &Blobs, req: &Request<Vec<u8>>) -> Response<Cow<'static, [u8]>> {
+    // 页面与自定义协议不同源，只允许 [Blobs::allow_origin] 设置的来源跨域读取；
+    // 先检查来源再取出，其他来源（含不带 Origin 的请求）无法读取或消耗响应
+    let builder = Response::builder().header(VARY, ORIGIN);
+    let Some(origin) = req
+        .headers()
+        .get(ORIGIN)
+        .and_then(|o| o.to_str().ok())
+        .filter(|origin| blobs.allows(origin))
+    else {
+        return builder
+            .status(StatusCode::FORBIDDEN)
+            .body(Cow::Borrowed(&[][..]))
+            .unwrap_or_else(|_| Response::new(Cow::Borrowed(&[][..])));
+    };
+    let builder = builder.header(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    let blob = blobs.take(req.uri().path().trim_start_matches('/'));
+    let response = match blob {
+        Some((mime, data)) => builder
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, mime)
+            .body(Cow::Owned(data)),
+        None => builder
+            .status(StatusCode::NOT_FOUND)
+            .body(Cow::Borrowed(&[][..])),
+    };
+    response.unwrap_or_else(|_| Response::new(Cow::Borrowed(&[][..])))
+}
+
 impl<H> WindowState<H> {
     pub fn get(&self) -> &H {
         &self.0
@@ -83,6 +137,12 @@ impl std::fmt::Display for WindowId {
 
 impl From<&TaoWindow> for WindowId {
     fn from(value: &TaoWindow) -> Self {
-        Self(hash!(&value.id()).to_string().into())
+        (&value.id()).into()
+    }
+}
+
+impl From<&tao::window::WindowId> for WindowId {
+    fn from(value: &tao::window::WindowId) -> Self {
+        Self(hash!(value).to_string().into())
     }
 }
@@ -1,29 +1,32 @@
 use crate::{
-    FnResult, RawMessage, WindowId, WindowRef, WindowState,
-    event::{IpcReq, IpcResp, SysWindowEvent, UserEvent},
+    BridgeHandlers, Emitter, FnResult, RawMessage, Source, WindowId, WindowLifecycle, WindowRef,
+    WindowState,
+    event::{IpcMsg, IpcResp, SysWindowEvent, UserEvent},
 };
 use dashmap::DashMap;
 use libcommon::prelude::*;
-use std::{pin::Pin, sync::Arc};
+use std::{collections::HashMap, pin::Pin, sync::Arc};
 use tao::{
-    event::Event,
+    event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop, EventLoopBuilder},
     window::WindowBuilder,
 };
+use tokio::sync::Semaphore;
 use wry::WebViewBuilder;
 
-/// 内部存储动态分发的类型
-type BoxedHandler<H> = Box<
-    dyn Fn(Option<RawMessage>, WindowState<H>) -> Pin<Box<dyn Future<Output = FnResult> + Send>>
-        + Send
-        + Sync,
->;
+/// 窗口生命周期事件的回调
+pub type Listener = Arc<dyn Fn(&WindowId, WindowLifecycle) + Send + Sync>;
+
+/// 所有窗口同时执行中的 IPC 命令数上限（批量请求按其中的命令数计），超出时直接回复 busy
+const MAX_PENDING_IPC: usize = 256;
 
 pub struct WindowManager<H> {
     wm: DashMap<WindowId, WindowRef>,
     event: EventLoop<UserEvent>,
-    handlers: Arc<DashMap<String, Arc<BoxedHandler<H>>>>,
+    handlers: BridgeHandlers<H>,
     state: WindowState<H>,
+    listener: Option<Listener>,
+    ipc: Arc<Semaphore>,
 }
 
 impl Default for WindowManager<()> {
@@ -33,6 +36,8 @@ impl Default for WindowManager<()> {
             event: EventLoopBuilder::with_user_event().build(),
             handlers: Default::default(),
             state: WindowState(().into()),
+            listener: None,
+            ipc: Arc::new(Semaphore::new(MAX_PENDING_IPC)),
         }
     }
 }
@@ -42,58 +47,113 @@ impl<H: Send + Sync + 'static> WindowManager<H> {
         Self {
             wm: DashMap::new(),
             event: EventLoopBuilder::with_user_event().build(),
-            handlers: Arc::new(DashMap::new()),
+            handlers: BridgeHandlers::default(),
             state: WindowState(state),
+            listener: None,
+            ipc: Arc::new(Semaphore::new(MAX_PENDING_IPC)),
         }
     }
 
     pub fn create<'a>(&self, win: WindowBuilder, web: WebViewBuilder<'a>) -> Result<WindowId> {
-        let wref = WindowRef::create(win, web, &self.event)?;
+        let wref = WindowRef::create(win, web, &self.event, self.handlers.blobs())?;
         let id = wref.id();
         self.wm.insert(id.clone(), wref);
+        notify(&self.listener, &id, WindowLifecycle::Created);
         Ok(id)
     }
 
+    /// 监听窗口的创建、获得/失去焦点、最小化和关闭
+    pub fn with_listener(
+        mut self,
+        listener: impl Fn(&WindowId, WindowLifecycle) + Send + Sync + 'static,
+    ) -> Self {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
     pub fn run(self) -> ! {
         let proxy = self.event.create_proxy();
+        let listener = self.listener.clone();
+        // 本轮事件循环中完成的响应，每个窗口合并为一次 evaluate_script
+        let mut pending: HashMap<WindowId, Vec<IpcResp>> = HashMap::new();
         self.event.run(move |event, _, flow| {
             *flow = ControlFlow::Wait;
             match event {
                 Event::Opened { urls } => {
                     debug!("open: {:?}", urls);
                 }
+                Event::WindowEvent {
+                    window_id,
+                    event: WindowEvent::Focused(focused),
+                    ..
+                } => {
+                    let event = match focused {
+                        true => WindowLifecycle::Focused,
+                        false => WindowLifecycle::Blurred,
+                    };
+                    notify(&listener, &(&window_id).into(), event);
+                }
+                Event::MainEventsCleared => {
+                    for (wid, resps) in pending.drain() {
+                        if let Some(w) = self.wm.get(&wid)
+                            && let Err(e) = w.resp2web(resps)
+                        {
+                            warn!("Failed to send ipc result to window({wid}): {e}");
+                        }
+                    }
+                }
                 Event::UserEvent(user_event) => match user_event {
                     UserEvent::IpcMessage(wid, msg) => {
-                        let ipcreq: IpcReq = match serde_json::from_str(&msg) {
-                            Ok(req) => req,
+                        let msg = match IpcMsg::parse(&msg) {
+                            Ok(msg) => msg,
                             Err(e) => return warn!("Failed to parse ipc message: {e}, ignore."),
                         };
-                        trace!("receiver IpcMessage: {ipcreq:?}");
-                        let cmd = ipcreq.command;
-                        let Some(fun) = self.handlers.get(&cmd).map(|v| Arc::clone(&v)) else {
-                            let resp = IpcResp::err(
-                                ipcreq.id,
-                                format!("No handler registered for command '{cmd}'"),
-                            );
-                            UserEvent::IcpResultSend(wid, resp).send(&proxy);
-                            return;
+                        let Ok(permit) = self.ipc.clone().try_acquire_many_owned(msg.cost() as u32)
+                        else {
+                            warn!("Too many pending ipc messages, reply busy to window({wid})");
+                            let busy = msg.ids().into_iter().map(|id| {
+                                IpcResp::err(
+                                    id,
+                                    "too many pending requests".to_string(),
+                                    Some("busy"),
+                                )
+                            });
+                            return pending.entry(wid).or_default().extend(busy);
                         };
                         let proxy = proxy.clone();
                         let state = self.state.clone();
+                        let handlers = self.handlers.clone();
                         tokio::spawn(async move {
-                            let resp = match fun(ipcreq.payload, state).await {
-                                Ok(res) => IpcResp::ok(ipcreq.id, res),
-                                Err(e) => IpcResp::err(ipcreq.id, format!("{e:?}")),
-                            };
-                            trace!("resp to: {wid}: {resp:?}");
-                            UserEvent::IcpResultSend(wid, resp).send(&proxy);
+                            let _permit = permit;
+                            let source = Source::Window(wid.clone());
+                            let resps = handlers.handle_msg(msg, state, &source).await;
+                            trace!("resp to: {wid}: {resps:?}");
+                            UserEvent::IcpResultSend(wid, resps).send(&proxy);
                         });
                     }
-                    UserEvent::IcpResultSend(wid, resp) => {
+                    UserEvent::IcpResultSend(wid, resps) => {
+                        pending.entry(wid).or_default().extend(resps);
+                    }
+                    UserEvent::Emit(event, payload) => {
+                        for w in self.wm.iter() {
+                            if let Err(e) = w.emit2web(&event, &payload) {
+                                warn!("Failed to emit {event} to window({}): {e}", w.key());
+                            }
+                        }
+                    }
+                    UserEvent::EmitTo(wid, event, payload) => {
                         if let Some(w) = self.wm.get(&wid)
-                            && let Err(e) = w.resp2web(resp)
+                            && let Err(e) = w.emit2web(&event, &payload)
                         {
-                            warn!("Failed to send ipc result to window({wid}): {e}");
+                            warn!("Failed to emit {event} to window({wid}): {e}");
+                        }
+                    }
+                    UserEvent::SetVisible(wid, visible) => {
+                        if let Some(w) = self.wm.get(&wid) {
+                            w.window.set_visible(visible);
+                            if visible {
+                                w.window.set_focus();
+                            }
                         }
                     }
                     UserEvent::SysWindowEvent(id, sys) => match sys {
@@ -108,8 +168,10 @@ impl<H: Send + Sync + 'static> WindowManager<H> {
                             if let Some(w) = self.wm.remove(&id) {
                                 drop(w);
                                 debug!("Window({id}) closed");
+                                notify(&listener, &id, WindowLifecycle::Closed);
                             }
-                            if self.wm.is_empty() {
+                            // 隐藏的窗口（如按需显示的对话框）不阻止退出
+                            if self.wm.iter().all(|w| !w.window.is_visible()) {
                                 info!("All windows closed, exiting");
                                 *flow = ControlFlow::Exit;
                             }
@@ -117,6 +179,7 @@ impl<H: Send + Sync + 'static> WindowManager<H> {
                         SysWindowEvent::Minimize => {
                             if let Some(r) = self.wm.get(&id) {
                                 r.window.set_minimized(true);
+                                notify(&listener, &id, WindowLifecycle::Minimized);
                             }
                         }
                     },
@@ -137,9 +200,29 @@ impl<H: Send + Sync + 'static> WindowManager<H> {
             + Sync
             + 'static,
     {
-        for (name, ele) in handlers {
-            self.handlers.insert(name, Arc::new(Box::new(ele)));
-        }
+        self.handlers.register(handlers);
+    }
+
+    /// 使用已有的命令表（与窗口之外的通道共享）
+    pub fn with_handlers(mut self, handlers: BridgeHandlers<H>) -> Self {
+        self.handlers = handlers;
+        self
+    }
+
+    /// 向前端发送事件的句柄，可在事件循环之外使用
+    pub fn emitter(&self) -> Emitter {
+        Emitter::new(self.event.create_proxy())
+    }
+
+    /// 命令表，可挂载到窗口之外的通道（如 HTTP/WebSocket）
+    pub fn handlers(&self) -> BridgeHandlers<H> {
+        self.handlers.clone()
+    }
+}
+
+fn notify(listener: &Option<Listener>, id: &WindowId, event: WindowLifecycle) {
+    if let Some(listener) = listener {
+        listener(id, event);
     }
 }
 